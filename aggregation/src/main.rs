@@ -0,0 +1,27 @@
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use zk_light_client_core::aggregation::{aggregate, AggregationInput};
+use zk_light_client_core::sha256_merkle::sha256_hash;
+use zk_light_client_core::{cycle_tracker_end, cycle_tracker_start};
+
+pub fn main() {
+    // Read the public values of the chunks, their proofs are read by the verify syscall.
+    let aggregation_input = sp1_zkvm::io::read::<AggregationInput>();
+
+    // recursively verify the proof of every chunk
+    cycle_tracker_start!("chunk proofs verification");
+    for chunk in aggregation_input.chunks.iter() {
+        sp1_zkvm::lib::verify::verify_sp1_proof(
+            &aggregation_input.chunk_vkey_digest,
+            &sha256_hash(&chunk.public_values.abi_encode()),
+        );
+    }
+    cycle_tracker_end!("chunk proofs verification");
+
+    // circuit logic, returns the ABI-encoded public values
+    let bytes = aggregate(aggregation_input);
+
+    // Commit to the public values of the program.
+    sp1_zkvm::io::commit_slice(&bytes);
+}
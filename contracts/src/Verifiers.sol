@@ -0,0 +1,8 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+// Compiles the SP1 verifiers deployed by the EVM test harness (script/tests/evm_test.rs) into out/,
+// after `forge install succinctlabs/sp1-contracts@v2.0.0 --no-commit`.
+import {SP1Verifier as SP1VerifierGroth16} from "@sp1-contracts/v2.0.0/SP1VerifierGroth16.sol";
+import {SP1Verifier as SP1VerifierPlonk} from "@sp1-contracts/v2.0.0/SP1VerifierPlonk.sol";
+import {SP1MockVerifier} from "@sp1-contracts/SP1MockVerifier.sol";
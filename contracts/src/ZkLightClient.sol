@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+import {ISP1Verifier} from "@sp1-contracts/ISP1Verifier.sol";
+
+/// @notice Reference light client following the chain proven by the program, one proof at a time.
+/// @dev Each proof must start at the current tip, so that the tip only moves along proven headers.
+contract ZkLightClient {
+    /// @dev Mirrors `ProofPublicInputs` of the core crate, ABI-encoded as the proof public values.
+    struct ProofPublicInputs {
+        bytes32 epochStateHash;
+        bytes32 safeBlockHash;
+        uint64 safeBlockHeight;
+        bytes32 tipBlockHash;
+        uint64 tipBlockHeight;
+        bytes32 tipEpochStateHash;
+        uint256 tipChainwork;
+        bytes32 blockHashesMerkleRoot;
+        uint8 commitmentScheme;
+        bytes32 prevMmrRoot;
+        bytes32 mmrRoot;
+        bytes32 checkpointsRoot;
+        bytes32 anchorVkeyDigest;
+        uint8 verificationMode;
+        bytes4 networkMagic;
+        uint32 programVersion;
+        uint32 ruleFlags;
+    }
+
+    /// @dev Mirrors `AggregationPublicValues` of the core crate.
+    struct AggregationPublicValues {
+        ProofPublicInputs chain;
+        bytes32 chunkVkeyDigest;
+        uint64 chunkCount;
+    }
+
+    ISP1Verifier public immutable verifier;
+    /// @notice vkey hash of the program, as printed by the `vkey` command.
+    bytes32 public immutable programVKey;
+    /// @notice vkey hash of the aggregation program, as printed by the `vkey` command.
+    bytes32 public immutable aggregationVKey;
+    /// @notice Digest of the program vkey the aggregation program verifies chunks against, as
+    /// printed by the `vkey` command.
+    bytes32 public immutable programVKeyDigest;
+    bytes4 public immutable networkMagic;
+    uint32 public immutable programVersion;
+    /// @notice Optional rules enforced by the program, e.g. checkpoint anchoring.
+    uint32 public immutable ruleFlags;
+
+    bytes32 public tipBlockHash;
+    uint64 public tipBlockHeight;
+    bytes32 public tipEpochStateHash;
+    bytes32 public blockHashesMerkleRoot;
+    /// @notice Root of the MMR of all the block hashes up to the tip.
+    bytes32 public mmrRoot;
+
+    event TipUpdated(bytes32 indexed tipBlockHash, uint64 tipBlockHeight);
+
+    constructor(
+        ISP1Verifier _verifier,
+        bytes32 _programVKey,
+        bytes32 _aggregationVKey,
+        bytes32 _programVKeyDigest,
+        bytes4 _networkMagic,
+        uint32 _programVersion,
+        uint32 _ruleFlags,
+        bytes32 trustedBlockHash,
+        uint64 trustedBlockHeight,
+        bytes32 trustedEpochStateHash,
+        bytes32 trustedMmrRoot
+    ) {
+        verifier = _verifier;
+        programVKey = _programVKey;
+        aggregationVKey = _aggregationVKey;
+        programVKeyDigest = _programVKeyDigest;
+        networkMagic = _networkMagic;
+        programVersion = _programVersion;
+        ruleFlags = _ruleFlags;
+        tipBlockHash = trustedBlockHash;
+        tipBlockHeight = trustedBlockHeight;
+        tipEpochStateHash = trustedEpochStateHash;
+        mmrRoot = trustedMmrRoot;
+    }
+
+    /// @notice Moves the tip to the tip of a proof built on top of the current tip.
+    function update(bytes calldata publicValues, bytes calldata proofBytes) external {
+        verifier.verifyProof(programVKey, publicValues, proofBytes);
+        _advance(abi.decode(publicValues, (ProofPublicInputs)));
+    }
+
+    /// @notice Moves the tip to the tip of an aggregation of chunk proofs built on top of the
+    /// current tip.
+    function updateAggregated(bytes calldata publicValues, bytes calldata proofBytes) external {
+        verifier.verifyProof(aggregationVKey, publicValues, proofBytes);
+        AggregationPublicValues memory aggregated =
+            abi.decode(publicValues, (AggregationPublicValues));
+
+        // the aggregation program verifies the chunks against the vkey digest given by the prover
+        require(aggregated.chunkVkeyDigest == programVKeyDigest, "Chunks of another program");
+        _advance(aggregated.chain);
+    }
+
+    function _advance(ProofPublicInputs memory inputs) internal {
+        require(
+            inputs.networkMagic == networkMagic && inputs.programVersion == programVersion
+                && inputs.ruleFlags == ruleFlags,
+            "Domain mismatch"
+        );
+        // sampled chains only carry a probabilistic guarantee
+        require(inputs.verificationMode == 0, "Chain is not fully verified");
+        require(
+            inputs.safeBlockHash == tipBlockHash && inputs.safeBlockHeight == tipBlockHeight
+                && inputs.epochStateHash == tipEpochStateHash,
+            "Proof doesn't start at the tip"
+        );
+        require(inputs.tipBlockHeight > tipBlockHeight, "Proof doesn't extend the tip");
+        require(inputs.prevMmrRoot == mmrRoot, "Proof doesn't extend the MMR");
+
+        tipBlockHash = inputs.tipBlockHash;
+        tipBlockHeight = inputs.tipBlockHeight;
+        tipEpochStateHash = inputs.tipEpochStateHash;
+        blockHashesMerkleRoot = inputs.blockHashesMerkleRoot;
+        mmrRoot = inputs.mmrRoot;
+        emit TipUpdated(inputs.tipBlockHash, inputs.tipBlockHeight);
+    }
+}
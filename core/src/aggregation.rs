@@ -0,0 +1,146 @@
+use crate::{vkey_digest_to_bytes, CircuitPublicValues, ProofPublicInputs, VerificationMode};
+
+use alloy_sol_types::private::FixedBytes;
+use alloy_sol_types::{sol, SolType};
+use serde::{Deserialize, Serialize};
+
+// Aggregation of the proofs of consecutive chunks of a range, proven in parallel. Every chunk but
+// the first one is `Anchor::Chained` on the tip of the previous chunk, which is checked here. The
+// aggregated public values are those of a single proof of the entire range.
+//
+// The chunk proofs are verified against the committed `chunk_vkey_digest`, see
+// `vkey_digest_to_bytes`. It is checked against the main program by
+// `check_aggregation_public_values` in lib and `ZkLightClient.updateAggregated`.
+
+// Public values of a chunk proof, with the block hashes of its block hashes merkle root
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AggregatedChunk {
+    pub public_values: CircuitPublicValues,
+    pub block_hashes: Vec<[u8; 32]>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AggregationInput {
+    // vkey digest of the program proving the chunks
+    pub chunk_vkey_digest: [u32; 8],
+    pub chunks: Vec<AggregatedChunk>,
+}
+
+sol! {
+    struct AggregationPublicValues {
+        ProofPublicInputs chain;
+        bytes32 chunk_vkey_digest;
+        uint64 chunk_count;
+    }
+}
+
+// checks the chunk commits exactly its block hashes
+fn assert_chunk(chunk: &AggregatedChunk) {
+    let public_values = &chunk.public_values;
+    assert_eq!(
+        public_values.verification_mode,
+        VerificationMode::Full,
+        "Only fully verified chunks are aggregated"
+    );
+    assert_eq!(
+        chunk.block_hashes.len() as u64,
+        public_values.tip_block_height - public_values.safe_block_height + 1,
+        "Chunk block hashes count mismatch"
+    );
+    assert!(
+        chunk.block_hashes.first() == Some(&public_values.safe_block_hash)
+            && chunk.block_hashes.last() == Some(&public_values.tip_block_hash),
+        "Chunk block hashes don't span the chunk"
+    );
+    assert_eq!(
+        public_values
+            .commitment_scheme
+            .block_hashes_root(&chunk.block_hashes),
+        public_values.block_hashes_merkle_root,
+        "Chunk block hashes root mismatch"
+    );
+}
+
+// checks the chunk starts at the tip of the previous one
+fn assert_chained(previous: &CircuitPublicValues, next: &CircuitPublicValues) {
+    assert_eq!(
+        next.safe_block_hash, previous.tip_block_hash,
+        "Chunk safe block is not the previous chunk tip"
+    );
+    assert_eq!(
+        next.safe_block_height, previous.tip_block_height,
+        "Chunk safe block height is not the previous chunk tip height"
+    );
+    assert_eq!(
+        next.epoch_state_hash, previous.tip_epoch_state_hash,
+        "Chunk epoch state is not the previous chunk tip state"
+    );
+    assert_eq!(
+        next.prev_mmr_root, previous.mmr_root,
+        "Chunk MMR does not extend the previous chunk MMR"
+    );
+    assert_eq!(
+        next.commitment_scheme, previous.commitment_scheme,
+        "Chunk commitment scheme mismatch"
+    );
+    assert_eq!(
+        next.domain_tag, previous.domain_tag,
+        "Chunk domain tag mismatch"
+    );
+    assert!(
+        next.checkpoints_root == [0u8; 32] && next.anchor_vkey_digest == [0u8; 32],
+        "Only the first chunk is anchored"
+    );
+}
+
+// The chunk proofs must be verified by the caller, returns the ABI-encoded aggregated public values
+pub fn aggregate(input: AggregationInput) -> Vec<u8> {
+    assert!(!input.chunks.is_empty(), "No chunk to aggregate");
+
+    // [1] every chunk commits its block hashes, and starts at the tip of the previous one
+    for chunk in input.chunks.iter() {
+        assert_chunk(chunk);
+    }
+    for pair in input.chunks.windows(2) {
+        assert_chained(&pair[0].public_values, &pair[1].public_values);
+    }
+
+    // [2] a first chunk anchored on a proven tip is anchored on a proof of the chunk program
+    let first = &input.chunks[0].public_values;
+    let chunk_vkey_digest = vkey_digest_to_bytes(&input.chunk_vkey_digest);
+    assert!(
+        first.anchor_vkey_digest == [0u8; 32] || first.anchor_vkey_digest == chunk_vkey_digest,
+        "First chunk is anchored on a proof of another program"
+    );
+
+    // [3] the safe block of a chunk is the tip of the previous one, it's only committed once
+    let last = &input.chunks[input.chunks.len() - 1].public_values;
+    let mut block_hashes = input.chunks[0].block_hashes.clone();
+    for chunk in input.chunks[1..].iter() {
+        block_hashes.extend_from_slice(&chunk.block_hashes[1..]);
+    }
+
+    let public_values = CircuitPublicValues::new(
+        first.epoch_state_hash,
+        first.safe_block_hash,
+        first.safe_block_height,
+        last.tip_block_hash,
+        last.tip_block_height,
+        last.tip_epoch_state_hash,
+        last.tip_chainwork,
+        first.commitment_scheme.block_hashes_root(&block_hashes),
+        first.commitment_scheme,
+        first.prev_mmr_root,
+        last.mmr_root,
+        first.checkpoints_root,
+        first.anchor_vkey_digest,
+        VerificationMode::Full,
+        first.domain_tag,
+    );
+
+    AggregationPublicValues::abi_encode(&AggregationPublicValues {
+        chain: public_values.abi_public_inputs(),
+        chunk_vkey_digest: FixedBytes::from(chunk_vkey_digest),
+        chunk_count: input.chunks.len() as u64,
+    })
+}
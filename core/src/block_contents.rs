@@ -0,0 +1,518 @@
+use crate::btc_light_client::{AsLittleEndianBytes, Block};
+use crate::keccak_merkle::keccak256_hash;
+use crate::sha256_merkle::{double_sha256, get_merkle_root_from_branch, hash_pairs, sha256_hash};
+use crate::transaction::{Reader, Transaction};
+use crate::{validate_block, CircuitInput, ProofPublicInputs};
+
+use alloy_sol_types::private::FixedBytes;
+use alloy_sol_types::{sol, SolType};
+use serde::{Deserialize, Serialize};
+
+// Claims about the contents of blocks of a fully verified header chain. The header chain public
+// values are committed along with the outputs of each claim.
+
+// Leading word of the block contents public values. The main program commits them under the same
+// vkey as the header chain public values, which start with the epoch state hash of the safe block:
+// the tag is not the hash of any epoch state, so neither kind can be taken for the other.
+pub const BLOCK_CONTENTS_PROOF_KIND: [u8; 32] = *b"zk_light_client:block_contents\0\0";
+
+// Consensus serialization of an entire block of the chain
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FullBlock {
+    pub height: u64,
+    pub bytes: Vec<u8>,
+}
+
+// Transaction of the block at `height`, with its merkle branch
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TransactionProof {
+    pub height: u64,
+    pub index: u32,
+    pub transaction: Vec<u8>,
+    pub branch: Vec<[u8; 32]>,
+}
+
+// Transaction of the block of `coinbase`, with its branch in the witness merkle tree (BIP141)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WitnessProof {
+    pub coinbase: TransactionProof,
+    pub index: u32,
+    pub transaction: Vec<u8>,
+    pub witness_branch: Vec<[u8; 32]>,
+}
+
+// Output `vout` of an included transaction
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PaymentProof {
+    pub transaction: TransactionProof,
+    pub vout: u32,
+}
+
+// Input `vin` of an included transaction
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpendProof {
+    pub transaction: TransactionProof,
+    pub vin: u32,
+}
+
+// Witness item `item` of input `vin` of a transaction, revealing the preimage of its sha256 hash.
+// The witness is only committed by the wtxid.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PreimageProof {
+    pub transaction: WitnessProof,
+    pub vin: u32,
+    pub item: u32,
+}
+
+// OP_RETURN output `vout` of an included transaction
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OpReturnProof {
+    pub transaction: TransactionProof,
+    pub vout: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockContentsInput {
+    pub circuit_input: CircuitInput,
+    pub full_blocks: Vec<FullBlock>,
+    // coinbase transactions proving the height of their block (BIP34)
+    pub coinbase_heights: Vec<TransactionProof>,
+    // transactions whose wtxid is committed by the coinbase of their block
+    pub witnesses: Vec<WitnessProof>,
+    // outputs paid by included transactions
+    pub payments: Vec<PaymentProof>,
+    // outpoints spent by included transactions
+    pub spends: Vec<SpendProof>,
+    // hash preimages revealed by included transactions, such as HTLC claims
+    pub preimages: Vec<PreimageProof>,
+    // payloads written in OP_RETURN outputs of included transactions, such as timestamps
+    pub op_returns: Vec<OpReturnProof>,
+}
+
+sol! {
+    struct ValidatedBlock {
+        uint64 height;
+        uint64 transaction_count;
+    }
+
+    struct CommittedWitness {
+        bytes32 wtxid;
+        bytes32 txid;
+        uint64 height;
+    }
+
+    struct Payment {
+        bytes32 txid;
+        uint32 vout;
+        uint64 value;
+        bytes32 script_pubkey_hash;
+        uint64 height;
+        uint64 confirmations;
+    }
+
+    struct OutPoint {
+        bytes32 txid;
+        uint32 vout;
+    }
+
+    struct Spend {
+        OutPoint spent_outpoint;
+        bytes32 spending_txid;
+        uint64 height;
+    }
+
+    struct Preimage {
+        bytes32 hash;
+        bytes preimage;
+        bytes32 txid;
+        uint64 height;
+    }
+
+    struct OpReturnAnchor {
+        bytes32 payload;
+        bytes32 block_hash;
+        uint32 timestamp;
+        uint64 height;
+        uint64 confirmations;
+    }
+
+    // committed as the parameters of the program output, so that `proof_kind` is its first word
+    struct BlockContentsPublicValues {
+        bytes32 proof_kind;
+        ProofPublicInputs chain;
+        ValidatedBlock[] validated_blocks;
+        uint64[] coinbase_heights;
+        CommittedWitness[] committed_witnesses;
+        Payment[] payments;
+        Spend[] spends;
+        Preimage[] preimages;
+        OpReturnAnchor[] op_return_anchors;
+        // root of the hashes of the ABI-encoded anchors with the commitment scheme of the chain
+        bytes32 op_return_anchors_root;
+    }
+}
+
+// header of the chain at the given height
+pub fn chain_block(circuit_input: &CircuitInput, height: u64) -> &Block {
+    let safe_block_height = circuit_input.public_values.safe_block_height;
+    assert!(
+        height >= safe_block_height && height - safe_block_height < circuit_input.utilized_blocks,
+        "Block is not in the proven chain"
+    );
+    &circuit_input.blocks[(height - safe_block_height) as usize]
+}
+
+// checks the transaction merkle root, rejecting the duplicated subtrees of CVE-2012-2459 that give
+// the same root for different transaction lists
+pub fn assert_transactions_root(txids: &[[u8; 32]], header: &Block) {
+    assert!(!txids.is_empty(), "Block without transactions");
+    let mut level = txids.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                assert!(
+                    pair.len() == 1 || pair[0] != pair[1],
+                    "Mutated transaction merkle tree"
+                );
+                // the last node of an odd level is paired with itself
+                hash_pairs(pair[0], *pair.last().unwrap())
+            })
+            .collect();
+    }
+    assert_eq!(
+        level[0].to_little_endian(),
+        header.merkle_root,
+        "Transactions don't match the header merkle root"
+    );
+}
+
+// checks the transaction is included in the block of its height, and parses it
+pub fn assert_transaction_inclusion<'a>(
+    circuit_input: &CircuitInput,
+    proof: &'a TransactionProof,
+) -> Transaction<'a> {
+    let header = chain_block(circuit_input, proof.height);
+    // 64-byte transactions could be inner nodes of the merkle tree
+    assert_ne!(
+        proof.transaction.len(),
+        64,
+        "64-byte transactions are ambiguous"
+    );
+    let transaction = Transaction::parse(&proof.transaction);
+    assert_eq!(
+        get_merkle_root_from_branch(transaction.txid(), proof.index, &proof.branch)
+            .to_little_endian(),
+        header.merkle_root,
+        "Transaction is not included in the block"
+    );
+    transaction
+}
+
+// script sig prefix of a coinbase at the given height, the height pushed as a CScriptNum
+pub fn bip34_height_push(height: u64) -> Vec<u8> {
+    match height {
+        0 => vec![0x00],
+        // OP_1 to OP_16
+        1..=16 => vec![0x50 + height as u8],
+        _ => {
+            let mut number = height.to_le_bytes().to_vec();
+            while number.last() == Some(&0) {
+                number.pop();
+            }
+            // keep the number positive
+            if number.last().unwrap() & 0x80 != 0 {
+                number.push(0);
+            }
+            let mut push = vec![number.len() as u8];
+            push.extend_from_slice(&number);
+            push
+        }
+    }
+}
+
+// checks the coinbase of the block commits to the height of the block in the proven chain
+fn assert_coinbase_height(circuit_input: &CircuitInput, proof: &TransactionProof) -> u64 {
+    assert_eq!(proof.index, 0, "Coinbase is the first transaction");
+    let coinbase = assert_transaction_inclusion(circuit_input, proof);
+    assert!(coinbase.is_coinbase(), "Transaction is not a coinbase");
+    assert!(
+        coinbase.inputs()[0]
+            .script_sig
+            .starts_with(&bip34_height_push(proof.height)),
+        "Coinbase height mismatch"
+    );
+    proof.height
+}
+
+// OP_RETURN push of 36 bytes, starting with 0xaa21a9ed
+const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+// witness commitment of a coinbase, in the last output matching the commitment header
+pub fn witness_commitment(coinbase: &Transaction) -> Option<[u8; 32]> {
+    coinbase
+        .outputs()
+        .iter()
+        .rev()
+        .find(|output| {
+            output.script_pubkey.len() >= 38
+                && output.script_pubkey.starts_with(&WITNESS_COMMITMENT_HEADER)
+        })
+        .map(|output| output.script_pubkey[6..38].try_into().unwrap())
+}
+
+// double sha256 of the witness merkle root and the witness reserved value of the coinbase
+pub fn compute_witness_commitment(witness_root: [u8; 32], reserved_value: &[u8]) -> [u8; 32] {
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&witness_root.to_little_endian());
+    bytes[32..].copy_from_slice(reserved_value);
+    double_sha256(&bytes)
+}
+
+// checks the wtxid of the transaction is committed by the coinbase of its block, and parses it
+pub fn assert_witness_inclusion<'a>(
+    circuit_input: &CircuitInput,
+    proof: &'a WitnessProof,
+) -> Transaction<'a> {
+    // [1] coinbase and its commitment
+    assert_eq!(proof.coinbase.index, 0, "Coinbase is the first transaction");
+    let coinbase = assert_transaction_inclusion(circuit_input, &proof.coinbase);
+    assert!(coinbase.is_coinbase(), "Transaction is not a coinbase");
+    let commitment = witness_commitment(&coinbase).expect("Coinbase has no witness commitment");
+    let coinbase_witness = &coinbase.witnesses()[0];
+    assert!(
+        coinbase_witness.len() == 1 && coinbase_witness[0].len() == 32,
+        "Invalid witness reserved value"
+    );
+
+    // [2] transaction in the witness merkle tree, where the coinbase wtxid is zero
+    assert_ne!(proof.index, 0, "Coinbase is not in the witness merkle tree");
+    assert_ne!(
+        proof.transaction.len(),
+        64,
+        "64-byte transactions are ambiguous"
+    );
+    let transaction = Transaction::parse(&proof.transaction);
+    let witness_root =
+        get_merkle_root_from_branch(transaction.wtxid(), proof.index, &proof.witness_branch);
+    assert_eq!(
+        compute_witness_commitment(witness_root, coinbase_witness[0]),
+        commitment,
+        "Transaction is not committed by the coinbase"
+    );
+    transaction
+}
+
+fn assert_witness(circuit_input: &CircuitInput, proof: &WitnessProof) -> CommittedWitness {
+    let transaction = assert_witness_inclusion(circuit_input, proof);
+
+    CommittedWitness {
+        wtxid: FixedBytes::from(transaction.wtxid()),
+        txid: FixedBytes::from(transaction.txid()),
+        height: proof.coinbase.height,
+    }
+}
+
+// number of blocks from the block at the given height to the tip of the proven chain, included
+pub fn confirmations(circuit_input: &CircuitInput, height: u64) -> u64 {
+    circuit_input.public_values.tip_block_height - height + 1
+}
+
+// parses the paid output, the script pubkey is committed by its keccak256 hash
+fn assert_payment(circuit_input: &CircuitInput, proof: &PaymentProof) -> Payment {
+    let transaction = assert_transaction_inclusion(circuit_input, &proof.transaction);
+    let outputs = transaction.outputs();
+    let output = outputs
+        .get(proof.vout as usize)
+        .expect("Output index out of range");
+
+    Payment {
+        txid: FixedBytes::from(transaction.txid()),
+        vout: proof.vout,
+        value: output.value,
+        script_pubkey_hash: FixedBytes::from(keccak256_hash(output.script_pubkey)),
+        height: proof.transaction.height,
+        confirmations: confirmations(circuit_input, proof.transaction.height),
+    }
+}
+
+// parses the outpoint spent by the input
+fn assert_spend(circuit_input: &CircuitInput, proof: &SpendProof) -> Spend {
+    let transaction = assert_transaction_inclusion(circuit_input, &proof.transaction);
+    assert!(!transaction.is_coinbase(), "Coinbase spends no outpoint");
+    let inputs = transaction.inputs();
+    let input = inputs
+        .get(proof.vin as usize)
+        .expect("Input index out of range");
+
+    Spend {
+        spent_outpoint: OutPoint {
+            txid: FixedBytes::from(input.prev_txid),
+            vout: input.prev_vout,
+        },
+        spending_txid: FixedBytes::from(transaction.txid()),
+        height: proof.transaction.height,
+    }
+}
+
+// parses the preimage from the witness stack, committed along with its sha256 hash
+fn assert_preimage(circuit_input: &CircuitInput, proof: &PreimageProof) -> Preimage {
+    let transaction = assert_witness_inclusion(circuit_input, &proof.transaction);
+    let witnesses = transaction.witnesses();
+    let preimage = witnesses
+        .get(proof.vin as usize)
+        .and_then(|witness| witness.get(proof.item as usize))
+        .expect("Witness item out of range");
+
+    Preimage {
+        hash: FixedBytes::from(sha256_hash(preimage)),
+        preimage: preimage.to_vec().into(),
+        txid: FixedBytes::from(transaction.txid()),
+        height: proof.transaction.coinbase.height,
+    }
+}
+
+// payload of an OP_RETURN script holding a single data push, of any push opcode
+pub fn op_return_payload(script_pubkey: &[u8]) -> &[u8] {
+    assert_eq!(
+        script_pubkey.first(),
+        Some(&0x6a),
+        "Output is not an OP_RETURN"
+    );
+    let mut reader = Reader::new(&script_pubkey[1..]);
+    let length = match reader.read(1)[0] {
+        length @ 0..=0x4b => length as usize,
+        // OP_PUSHDATA1, OP_PUSHDATA2, OP_PUSHDATA4
+        0x4c => reader.read(1)[0] as usize,
+        0x4d => u16::from_le_bytes(reader.read_array()) as usize,
+        0x4e => u32::from_le_bytes(reader.read_array()) as usize,
+        _ => panic!("OP_RETURN payload is not a push"),
+    };
+    let payload = reader.read(length);
+    assert!(reader.is_empty(), "OP_RETURN holds more than one push");
+    payload
+}
+
+// parses the OP_RETURN payload, committed with the header of its block
+fn assert_op_return(circuit_input: &CircuitInput, proof: &OpReturnProof) -> OpReturnAnchor {
+    let transaction = assert_transaction_inclusion(circuit_input, &proof.transaction);
+    let outputs = transaction.outputs();
+    let output = outputs
+        .get(proof.vout as usize)
+        .expect("Output index out of range");
+    let header = chain_block(circuit_input, proof.transaction.height);
+    let payload: [u8; 32] = op_return_payload(output.script_pubkey)
+        .try_into()
+        .expect("OP_RETURN payload is not 32 bytes");
+
+    OpReturnAnchor {
+        payload: FixedBytes::from(payload),
+        block_hash: FixedBytes::from(header.compute_block_hash()),
+        timestamp: u32::from_le_bytes(header.time),
+        height: proof.transaction.height,
+        confirmations: confirmations(circuit_input, proof.transaction.height),
+    }
+}
+
+// checks the block matches the header of its height, and returns the number of transactions
+fn assert_full_block(circuit_input: &CircuitInput, full_block: &FullBlock) -> u64 {
+    let header = chain_block(circuit_input, full_block.height);
+    let mut reader = Reader::new(&full_block.bytes);
+    assert_eq!(
+        reader.read(80),
+        header.serialize(),
+        "Block does not match the proven header"
+    );
+
+    let transaction_count = reader.read_compact_size();
+    let mut txids = Vec::with_capacity(transaction_count as usize);
+    for _ in 0..transaction_count {
+        txids.push(Transaction::read(&mut reader).txid());
+    }
+    assert!(reader.is_empty(), "Trailing data after block");
+
+    cycle_tracker_start!("transactions root");
+    assert_transactions_root(&txids, header);
+    cycle_tracker_end!("transactions root");
+    transaction_count
+}
+
+pub fn validate_block_contents(input: BlockContentsInput) -> Vec<u8> {
+    // [1] header chain
+    let public_values = validate_block(input.circuit_input);
+
+    // [2] full blocks
+    let validated_blocks = input
+        .full_blocks
+        .iter()
+        .map(|full_block| ValidatedBlock {
+            height: full_block.height,
+            transaction_count: assert_full_block(&input.circuit_input, full_block),
+        })
+        .collect();
+
+    // [3] coinbase heights
+    let coinbase_heights = input
+        .coinbase_heights
+        .iter()
+        .map(|proof| assert_coinbase_height(&input.circuit_input, proof))
+        .collect();
+
+    // [4] witness commitments
+    let committed_witnesses = input
+        .witnesses
+        .iter()
+        .map(|proof| assert_witness(&input.circuit_input, proof))
+        .collect();
+
+    // [5] payments
+    let payments = input
+        .payments
+        .iter()
+        .map(|proof| assert_payment(&input.circuit_input, proof))
+        .collect();
+
+    // [6] spends
+    let spends = input
+        .spends
+        .iter()
+        .map(|proof| assert_spend(&input.circuit_input, proof))
+        .collect();
+
+    // [7] preimages
+    let preimages = input
+        .preimages
+        .iter()
+        .map(|proof| assert_preimage(&input.circuit_input, proof))
+        .collect();
+
+    // [8] OP_RETURN anchors, batched in a tree so that each one can be checked with a branch
+    let op_return_anchors = input
+        .op_returns
+        .iter()
+        .map(|proof| assert_op_return(&input.circuit_input, proof))
+        .collect::<Vec<_>>();
+    let op_return_anchors_root = public_values.commitment_scheme.block_hashes_root(
+        &op_return_anchors
+            .iter()
+            .map(|anchor| {
+                public_values
+                    .commitment_scheme
+                    .hash(&OpReturnAnchor::abi_encode(anchor))
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    BlockContentsPublicValues::abi_encode_params(&BlockContentsPublicValues {
+        proof_kind: FixedBytes::from(BLOCK_CONTENTS_PROOF_KIND),
+        chain: public_values.abi_public_inputs(),
+        validated_blocks,
+        coinbase_heights,
+        committed_witnesses,
+        payments,
+        spends,
+        preimages,
+        op_return_anchors,
+        op_return_anchors_root: FixedBytes::from(op_return_anchors_root),
+    })
+}
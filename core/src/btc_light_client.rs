@@ -1,10 +1,13 @@
-use crate::sha256_merkle::get_merkle_root;
-use crate::constants::EXPECTED_EPOCH_SECONDS;
+use crate::sha256_merkle::{double_sha256, sha256_hash};
+use crate::constants::{
+    EPOCH_BLOCK_NUMBER, EXPECTED_EPOCH_SECONDS, MEDIAN_TIME_SPAN, MIN_DIFFICULTY_BLOCK_SPACING,
+};
+use crate::genesis::Network;
+use crate::CircuitPublicValues;
 
 use crypto_bigint::U256;
-use crypto_bigint::CheckedMul;
+use crypto_bigint::{CheckedMul, Encoding};
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
 
 pub trait AsLittleEndianBytes {
     fn to_little_endian(self) -> Self;
@@ -29,24 +32,121 @@ pub struct Block {
 }
 
 impl Block {
-    pub fn serialize(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(80);
-        bytes.extend_from_slice(&self.version);
-        bytes.extend_from_slice(&self.prev_blockhash);
-        bytes.extend_from_slice(&self.merkle_root);
-        bytes.extend_from_slice(&self.time);
-        bytes.extend_from_slice(&self.bits);
-        bytes.extend_from_slice(&self.nonce);
-
-        assert_eq!(bytes.len(), 80, "Header must be exactly 80 bytes");
+    pub fn serialize(&self) -> [u8; 80] {
+        let mut bytes = [0u8; 80];
+        bytes[0..4].copy_from_slice(&self.version);
+        bytes[4..36].copy_from_slice(&self.prev_blockhash);
+        bytes[36..68].copy_from_slice(&self.merkle_root);
+        bytes[68..72].copy_from_slice(&self.time);
+        bytes[72..76].copy_from_slice(&self.bits);
+        bytes[76..80].copy_from_slice(&self.nonce);
         bytes
     }
 
+    pub fn from_bytes(header: &[u8; 80], height: u64) -> Self {
+        Self {
+            height,
+            version: header[0..4].try_into().unwrap(),
+            prev_blockhash: header[4..36].try_into().unwrap(),
+            merkle_root: header[36..68].try_into().unwrap(),
+            time: header[68..72].try_into().unwrap(),
+            bits: header[72..76].try_into().unwrap(),
+            nonce: header[76..80].try_into().unwrap(),
+        }
+    }
+
     pub fn compute_block_hash(&self) -> [u8; 32] {
-        let header = self.serialize();
-        let first_hash = Sha256::digest(header);
-        let second_hash: [u8; 32] = Sha256::digest(first_hash).into();
-        second_hash.to_little_endian()
+        double_sha256(&self.serialize()).to_little_endian()
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EpochState {
+    pub epoch_start_height: u64,
+    pub epoch_start_time: [u8; 4],
+    pub bits: [u8; 4],
+    // cumulative chainwork (big-endian) up to and including the last accumulated block
+    pub chainwork: [u8; 32],
+    // timestamps of the last accumulated blocks, oldest first
+    pub timestamps: [[u8; 4]; MEDIAN_TIME_SPAN],
+}
+
+impl EpochState {
+    pub fn new(
+        retarget_block: &Block,
+        chainwork: U256,
+        timestamps: [[u8; 4]; MEDIAN_TIME_SPAN],
+    ) -> Self {
+        Self {
+            epoch_start_height: retarget_block.height,
+            epoch_start_time: retarget_block.time,
+            bits: retarget_block.bits,
+            chainwork: chainwork.to_be_bytes(),
+            timestamps,
+        }
+    }
+
+    // the epoch state of a chain anchored on `anchor_block`, only the anchor block itself is
+    // accounted for in the chainwork and the timestamp history, see `median_time_past`
+    pub fn from_anchor(anchor_block: &Block, epoch_start_time: [u8; 4]) -> Self {
+        let mut timestamps = [[0u8; 4]; MEDIAN_TIME_SPAN];
+        timestamps[MEDIAN_TIME_SPAN - 1] = anchor_block.time;
+        Self {
+            epoch_start_height: anchor_block.height
+                - anchor_block.height % EPOCH_BLOCK_NUMBER as u64,
+            epoch_start_time,
+            bits: anchor_block.bits,
+            chainwork: block_work(anchor_block.bits).to_be_bytes(),
+            timestamps,
+        }
+    }
+
+    pub fn serialize(&self) -> [u8; 48 + 4 * MEDIAN_TIME_SPAN] {
+        let mut bytes = [0u8; 48 + 4 * MEDIAN_TIME_SPAN];
+        bytes[0..8].copy_from_slice(&self.epoch_start_height.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.epoch_start_time);
+        bytes[12..16].copy_from_slice(&self.bits);
+        bytes[16..48].copy_from_slice(&self.chainwork);
+        for (i, time) in self.timestamps.iter().enumerate() {
+            bytes[48 + 4 * i..52 + 4 * i].copy_from_slice(time);
+        }
+        bytes
+    }
+
+    pub fn compute_state_hash(&self) -> [u8; 32] {
+        sha256_hash(&self.serialize())
+    }
+
+    // median of the accumulated timestamps. Fewer than `MEDIAN_TIME_SPAN` blocks are known after
+    // genesis or an anchor, the missing timestamps are zero and left out as Bitcoin Core does for
+    // the first blocks of the chain
+    pub fn median_time_past(&self) -> u32 {
+        let mut times = self.timestamps.map(u32::from_le_bytes);
+        times.sort_unstable();
+        let missing = times.iter().take_while(|&&time| time == 0).count();
+        times[missing + (MEDIAN_TIME_SPAN - missing) / 2]
+    }
+
+    pub fn begin_epoch(&mut self, retarget_block: &Block) {
+        self.epoch_start_height = retarget_block.height;
+        self.epoch_start_time = retarget_block.time;
+        self.bits = retarget_block.bits;
+    }
+
+    pub fn accumulate(&mut self, block: &Block) {
+        self.chainwork = U256::from_be_slice(&self.chainwork)
+            .wrapping_add(&block_work(block.bits))
+            .to_be_bytes();
+        self.timestamps.rotate_left(1);
+        self.timestamps[MEDIAN_TIME_SPAN - 1] = block.time;
+    }
+
+    // move the state forward by one block, assuming the block has already been validated
+    pub fn advance(&mut self, block: &Block) {
+        if block.height % EPOCH_BLOCK_NUMBER as u64 == 0 {
+            self.begin_epoch(block);
+        }
+        self.accumulate(block);
     }
 }
 
@@ -68,32 +168,66 @@ pub fn bits_to_target(bits: [u8; 4]) -> U256 {
     }
 }
 
-pub fn assert_pow(proposed_block_hash: &[u8; 32], proposed_block: &Block, proposed_target: U256) {
-    let calculated_block_hash = proposed_block.compute_block_hash();
-
-    // [2] verify proposed block hash matches calculated block hash
-    assert_eq!(calculated_block_hash, *proposed_block_hash);
+// work = 2**256 / (target + 1), taken from rust-bitcoin
+pub fn block_work(bits: [u8; 4]) -> U256 {
+    let target = bits_to_target(bits);
+    target
+        .not()
+        .wrapping_div(&target.wrapping_add(&U256::ONE))
+        .wrapping_add(&U256::ONE)
+}
 
-    // [3] verify PoW -> block hash <= proposed target
+// private so that the hash can't be given apart from the header it was computed from
+fn assert_pow(block_hash: &[u8; 32], target: U256) {
+    // verify PoW -> block hash <= target
+    cycle_tracker_start!("pow");
     assert!(
-        U256::from_be_slice(proposed_block_hash).le(&proposed_target),
+        U256::from_be_slice(block_hash).le(&target),
         "PoW invalid hash < target"
     );
+    cycle_tracker_end!("pow");
+}
+
+// hashes the header once and checks its PoW against its own target, returns the block hash
+pub fn assert_block_pow(block: &Block) -> [u8; 32] {
+    cycle_tracker_start!("header hashing");
+    let block_hash = block.compute_block_hash();
+    cycle_tracker_end!("header hashing");
+    assert_pow(&block_hash, bits_to_target(block.bits));
+    block_hash
 }
 
+pub fn assert_pow_limit(target: U256, network: Network) {
+    assert!(
+        target <= bits_to_target(network.pow_limit_bits()),
+        "Target is easier than the PoW limit"
+    );
+}
+
+// returns the hash of the proposed block, to be carried to the next one
 pub fn verify_block(
-    proposed_block_hash: [u8; 32],
     previous_block_hash: [u8; 32],
     proposed_block: &Block,
-    retarget_block: &Block,
+    epoch_state: &EpochState,
     previous_block_height: u64,
-) {
-    // [1] verify proposed target is equal to real target
-    let proposed_target = bits_to_target(proposed_block.bits);
+    network: Network,
+) -> [u8; 32] {
+    // [1] verify proposed target is equal to real target, the epoch state ends with the parent
+    let previous_block_time = u32::from_le_bytes(epoch_state.timestamps[MEDIAN_TIME_SPAN - 1]);
+    let min_difficulty_block = network.allows_min_difficulty_blocks()
+        && proposed_block.height % EPOCH_BLOCK_NUMBER as u64 != 0
+        && u32::from_le_bytes(proposed_block.time) as u64
+            > previous_block_time as u64 + MIN_DIFFICULTY_BLOCK_SPACING as u64;
+    let real_bits = if min_difficulty_block {
+        network.pow_limit_bits()
+    } else {
+        epoch_state.bits
+    };
     assert_eq!(
-        retarget_block.bits, proposed_block.bits,
+        real_bits, proposed_block.bits,
         "Proposed target does not match real target"
     );
+    assert_pow_limit(bits_to_target(proposed_block.bits), network);
 
     // [2] verify the proposed block height is one greater than previous_block_height
     assert_eq!(
@@ -109,87 +243,194 @@ pub fn verify_block(
         "Proposed prev_block hash does not match real prev_block hash"
     );
 
-    // [4] verify PoW (double sha256(block_hash) <= target)
-    assert_pow(&proposed_block_hash, proposed_block, proposed_target);
+    // [4] verify the proposed timestamp is greater than the median time past
+    assert!(
+        u32::from_le_bytes(proposed_block.time) > epoch_state.median_time_past(),
+        "Block time is not greater than median time past"
+    );
+
+    // [5] verify PoW (double sha256(block_hash) <= target)
+    assert_block_pow(proposed_block)
+}
+
+// taken from rust-bitcoin (GetCompact)
+pub fn target_to_bits(target: U256) -> [u8; 4] {
+    let target = target.to_be_bytes();
+    let mut size = 32 - target.iter().take_while(|&&byte| byte == 0).count();
+    let mut mantissa = [0u8; 4];
+    for (i, byte) in target[32 - size..].iter().take(3).enumerate() {
+        mantissa[i + 1] = *byte;
+    }
+    let mut compact = u32::from_be_bytes(mantissa);
+    // the sign bit is set, move one byte into the exponent
+    if compact & 0x0080_0000 != 0 {
+        compact >>= 8;
+        size += 1;
+    }
+    (compact | (size as u32) << 24).to_le_bytes()
 }
 
 pub fn assert_target_bits(
-    last_epoch_begin_block: &Block,
+    last_epoch: &EpochState,
     last_epoch_end_block: &Block,
     new_epoch_begin_block: &Block,
+    network: Network,
 ) {
-    let old_target_difficulty = bits_to_target(
-        last_epoch_begin_block.bits
-    );
+    // [0] without retargeting the target of the last block is kept
+    if !network.retargets() {
+        assert_eq!(
+            last_epoch_end_block.bits, new_epoch_begin_block.bits,
+            "New epoch target bits mismatch"
+        );
+        return;
+    }
+
+    // [1] the actual timespan of the last epoch is bounded to a factor of 4 of the expected one
+    let actual_timespan = (u32::from_le_bytes(last_epoch_end_block.time) as i64
+        - u32::from_le_bytes(last_epoch.epoch_start_time) as i64)
+        .clamp(
+            EXPECTED_EPOCH_SECONDS as i64 / 4,
+            EXPECTED_EPOCH_SECONDS as i64 * 4,
+        );
+
+    // [2] the new target is scaled by the timespan, and can't be easier than the PoW limit. The
+    // old target is the one of the last block, which is the epoch target unless testnet ended the
+    // epoch with a min-difficulty block
+    let old_target_difficulty = bits_to_target(last_epoch_end_block.bits);
     let new_target_difficulty = old_target_difficulty
-        .checked_mul(&U256::from_u32(
-            u32::from_le_bytes(last_epoch_end_block.time) - u32::from_le_bytes(last_epoch_begin_block.time),
-        ))
+        .checked_mul(&U256::from_u32(actual_timespan as u32))
         .unwrap()
         .checked_div(&U256::from_u32(EXPECTED_EPOCH_SECONDS))
-        .unwrap();
+        .unwrap()
+        .min(bits_to_target(network.pow_limit_bits()));
 
-    let new_bits = u32::from_le_bytes(
-        new_epoch_begin_block.bits
+    // [3] verify the new epoch bits are the compact encoding of the new target
+    assert_eq!(
+        target_to_bits(new_target_difficulty),
+        new_epoch_begin_block.bits,
+        "New epoch target bits mismatch"
     );
-    let (mant, expt) = (new_bits >> 24, new_bits & 0xFFFFFF);
-    if mant <= 3 {
-        assert_eq!(
-            new_target_difficulty,
-            U256::from_u32(expt) >> (8 * (3 - mant) as usize)
-        );
-    } else {
-        assert_eq!(
-            new_target_difficulty >> (8 * (mant - 3) as usize),
-            U256::from_u32(expt)
-        );
-    }
 }
 
-pub fn assert_blockchain(
-    commited_block_hashes_merkle_root: [u8; 32],
+pub fn assert_safe_block(
+    safe_block_hash: [u8; 32],
     safe_block_height: u64,
-    retarget_block_hash: [u8; 32],
-    blocks: Vec<Block>,
-    retarget_block: Block,
+    safe_block: &Block,
+    epoch_state: &EpochState,
+    network: Network,
 ) {
-    // check committed retarget block
+    // [1] verify the safe block is the committed one
+    assert_eq!(
+        safe_block.height, safe_block_height,
+        "Safe block height mismatch"
+    );
+
+    // [2] verify the epoch state describes the chain up to the safe block
+    assert_eq!(
+        epoch_state.epoch_start_height,
+        safe_block_height - safe_block_height % EPOCH_BLOCK_NUMBER as u64,
+        "Epoch state does not begin the epoch of the safe block"
+    );
+    assert_eq!(
+        epoch_state.bits, safe_block.bits,
+        "Safe block target does not match real target"
+    );
     assert_eq!(
-        retarget_block.compute_block_hash(),
-        retarget_block_hash,
-        "Initial Retarget block hash mismatch"
+        epoch_state.timestamps[MEDIAN_TIME_SPAN - 1],
+        safe_block.time,
+        "Epoch state does not end with the safe block"
     );
 
-    let mut last_retarget_block = retarget_block;
-    let mut block_hashes = vec![];
+    // [3] verify safe block hash and PoW, so that a proof can't be anchored on a fabricated header
+    assert_eq!(
+        assert_block_pow(safe_block),
+        safe_block_hash,
+        "Safe block hash mismatch"
+    );
+    assert_pow_limit(bits_to_target(safe_block.bits), network);
+}
+
+pub fn assert_blockchain(
+    public_values: &CircuitPublicValues,
+    blocks: &[Block],
+    epoch_state: EpochState,
+    network: Network,
+) -> Vec<[u8; 32]> {
+    // check committed epoch state
+    assert_eq!(
+        epoch_state.compute_state_hash(),
+        public_values.epoch_state_hash,
+        "Initial epoch state hash mismatch"
+    );
+
+    // check committed safe block
+    assert_safe_block(
+        public_values.safe_block_hash,
+        public_values.safe_block_height,
+        &blocks[0],
+        &epoch_state,
+        network,
+    );
+
+    let mut epoch_state = epoch_state;
+    let mut current_block_hash = public_values.safe_block_hash;
+    let mut block_hashes = Vec::with_capacity(blocks.len());
+    block_hashes.push(current_block_hash);
     // the first block in this array is a safe block aka known to the contract
     for i in 0..blocks.len() - 1 {
         let current_block = &blocks[i];
         let next_block = &blocks[i + 1];
-        let current_block_hash = current_block.compute_block_hash();
-        let next_block_hash = next_block.compute_block_hash();
-        block_hashes.push(current_block_hash);
 
         // check target bits
-        if next_block.height % 2016 == 0 {
-            assert_target_bits(&last_retarget_block, current_block, next_block);
-            last_retarget_block = *next_block;
+        if next_block.height % EPOCH_BLOCK_NUMBER as u64 == 0 {
+            cycle_tracker_start!("retarget");
+            assert_target_bits(&epoch_state, current_block, next_block, network);
+            cycle_tracker_end!("retarget");
+            epoch_state.begin_epoch(next_block);
         }
 
-        // check block header
-        verify_block(
-            next_block_hash,
+        // check block header, each header is hashed once and its hash carried forward
+        current_block_hash = verify_block(
             current_block_hash,
             next_block,
-            &last_retarget_block,
-            safe_block_height + i as u64,
+            &epoch_state,
+            public_values.safe_block_height + i as u64,
+            network,
         );
+        epoch_state.accumulate(next_block);
+
+        block_hashes.push(current_block_hash);
     }
-    block_hashes.push(blocks.last().unwrap().compute_block_hash());
+
+    // check committed tip
+    assert_eq!(
+        public_values.tip_block_hash, current_block_hash,
+        "Tip block hash mismatch"
+    );
+    assert_eq!(
+        public_values.tip_block_height,
+        blocks.last().unwrap().height,
+        "Tip block height mismatch"
+    );
+    assert_eq!(
+        public_values.tip_epoch_state_hash,
+        epoch_state.compute_state_hash(),
+        "Tip epoch state hash mismatch"
+    );
+    assert_eq!(
+        public_values.tip_chainwork, epoch_state.chainwork,
+        "Tip chainwork mismatch"
+    );
 
     // check committed merkle root of block hashes
+    cycle_tracker_start!("block hashes root");
     assert_eq!(
-        commited_block_hashes_merkle_root,
-        get_merkle_root(block_hashes)
+        public_values.block_hashes_merkle_root,
+        public_values
+            .commitment_scheme
+            .block_hashes_root(&block_hashes)
     );
+    cycle_tracker_end!("block hashes root");
+
+    block_hashes
 }
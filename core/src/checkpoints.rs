@@ -0,0 +1,161 @@
+use crate::btc_light_client::{Block, EpochState};
+use crate::sha256_merkle::sha256_hash;
+use crate::{vkey_digest_to_bytes, Anchor, CircuitPublicValues, VerificationMode};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub height: u64,
+    pub hash: [u8; 32],
+    pub bits: [u8; 4],
+    pub epoch_start_time: [u8; 4],
+}
+
+impl Checkpoint {
+    pub fn serialize(&self) -> [u8; 48] {
+        let mut bytes = [0u8; 48];
+        bytes[0..8].copy_from_slice(&self.height.to_le_bytes());
+        bytes[8..40].copy_from_slice(&self.hash);
+        bytes[40..44].copy_from_slice(&self.bits);
+        bytes[44..48].copy_from_slice(&self.epoch_start_time);
+        bytes
+    }
+
+    pub fn epoch_state(&self, checkpoint_block: &Block) -> EpochState {
+        assert_eq!(checkpoint_block.bits, self.bits, "Checkpoint bits mismatch");
+        EpochState::from_anchor(checkpoint_block, self.epoch_start_time)
+    }
+}
+
+// Well-known mainnet blocks a proof is allowed to be anchored on, sorted by height. Block hashes
+// are in the same byte order as `Block::compute_block_hash`. New entries must be taken from a fully
+// validating node, and any change to this table changes `checkpoints_root`.
+pub const MAINNET_CHECKPOINTS: &[Checkpoint] = &[
+    // genesis
+    Checkpoint {
+        height: 0,
+        hash: [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x19, 0xd6, 0x68, 0x9c, 0x08, 0x5a, 0xe1, 0x65, 0x83,
+            0x1e, 0x93, 0x4f, 0xf7, 0x63, 0xae, 0x46, 0xa2, 0xa6, 0xc1, 0x72, 0xb3, 0xf1, 0xb6,
+            0x0a, 0x8c, 0xe2, 0x6f,
+        ],
+        bits: [0xff, 0xff, 0x00, 0x1d],
+        epoch_start_time: [0x29, 0xab, 0x5f, 0x49],
+    },
+];
+
+pub fn checkpoints_root() -> [u8; 32] {
+    let mut bytes = Vec::with_capacity(48 * MAINNET_CHECKPOINTS.len());
+    for checkpoint in MAINNET_CHECKPOINTS {
+        bytes.extend_from_slice(&checkpoint.serialize());
+    }
+    sha256_hash(&bytes)
+}
+
+// latest checkpoint at or below `height`, genesis being below every height
+pub fn latest_checkpoint(height: u64) -> &'static Checkpoint {
+    MAINNET_CHECKPOINTS
+        .iter()
+        .rev()
+        .find(|checkpoint| checkpoint.height <= height)
+        .expect("No checkpoint at or below height")
+}
+
+pub fn find_checkpoint(height: u64, hash: [u8; 32]) -> Option<&'static Checkpoint> {
+    MAINNET_CHECKPOINTS
+        .iter()
+        .find(|checkpoint| checkpoint.height == height && checkpoint.hash == hash)
+}
+
+// Checks that the chain starts either on a compiled-in checkpoint or on the tip of a previous
+// proof. The previous proof itself is not verified here, the program is responsible for verifying
+// it recursively against the vkey digest committed as `anchor_vkey_digest`, see
+// `vkey_digest_to_bytes`.
+pub fn assert_anchor(
+    public_values: &CircuitPublicValues,
+    safe_block: &Block,
+    epoch_state: &EpochState,
+    anchor: &Anchor,
+) {
+    if !matches!(anchor, Anchor::Chained) {
+        assert_eq!(
+            public_values.checkpoints_root,
+            checkpoints_root(),
+            "Checkpoints root mismatch"
+        );
+    }
+
+    match anchor {
+        Anchor::Checkpoint => {
+            let checkpoint = find_checkpoint(
+                public_values.safe_block_height,
+                public_values.safe_block_hash,
+            )
+            .expect("Safe block is not a checkpoint");
+            assert_eq!(
+                checkpoint.epoch_state(safe_block),
+                *epoch_state,
+                "Epoch state does not match checkpoint"
+            );
+            assert_eq!(
+                public_values.anchor_vkey_digest, [0u8; 32],
+                "Checkpoint anchor must not commit a vkey"
+            );
+        }
+        Anchor::ProvenTip {
+            vkey_digest,
+            public_values: previous,
+        } => {
+            let vkey_digest_bytes = vkey_digest_to_bytes(vkey_digest);
+            assert_eq!(
+                public_values.anchor_vkey_digest, vkey_digest_bytes,
+                "Anchor vkey digest mismatch"
+            );
+            // the previous proof must itself be anchored on a checkpoint or on this same program
+            assert!(
+                previous.anchor_vkey_digest == [0u8; 32]
+                    || previous.anchor_vkey_digest == vkey_digest_bytes,
+                "Previous proof is anchored on a foreign program"
+            );
+            assert_eq!(
+                previous.checkpoints_root, public_values.checkpoints_root,
+                "Previous proof trusted a different checkpoint set"
+            );
+            assert_eq!(
+                previous.domain_tag, public_values.domain_tag,
+                "Previous proof was made for another domain"
+            );
+            assert_eq!(
+                previous.verification_mode,
+                VerificationMode::Full,
+                "Previous proof did not verify every header"
+            );
+            assert_eq!(
+                previous.commitment_scheme, public_values.commitment_scheme,
+                "Previous proof used another commitment scheme"
+            );
+            assert_eq!(
+                previous.mmr_root, public_values.prev_mmr_root,
+                "Previous MMR root is not the previous proof MMR root"
+            );
+            assert_eq!(
+                previous.tip_block_hash, public_values.safe_block_hash,
+                "Safe block is not the previous proof tip"
+            );
+            assert_eq!(
+                previous.tip_block_height, public_values.safe_block_height,
+                "Safe block height is not the previous proof tip height"
+            );
+            assert_eq!(
+                previous.tip_epoch_state_hash, public_values.epoch_state_hash,
+                "Epoch state is not the previous proof tip state"
+            );
+        }
+        Anchor::Chained => {
+            assert!(
+                public_values.checkpoints_root == [0u8; 32]
+                    && public_values.anchor_vkey_digest == [0u8; 32],
+                "Chained proofs are not anchored"
+            );
+        }
+    }
+}
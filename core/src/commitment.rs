@@ -0,0 +1,188 @@
+use core::marker::PhantomData;
+
+use crate::keccak_merkle::{keccak256_hash, Keccak256BlockHashes};
+use crate::sha256_merkle::{sha256_hash, Sha256BlockHashes};
+
+use serde::{Deserialize, Serialize};
+
+// Block hashes are committed with a domain separated tree instead of the Bitcoin transaction
+// merkle tree: leaves and inner nodes are tagged, the last node of an odd level is promoted as is
+// instead of being duplicated, and the leaf count is bound in the root. So that a root commits to
+// exactly one list of block hashes (CVE-2012-2459).
+pub const LEAF_TAG: u8 = 0x00;
+pub const NODE_TAG: u8 = 0x01;
+pub const ROOT_TAG: u8 = 0x02;
+
+// Hash function a block hashes tree is built with
+pub trait TreeHasher {
+    fn hash(bytes: &[u8]) -> [u8; 32];
+}
+
+pub struct BlockHashesTree<H>(PhantomData<H>);
+
+impl<H: TreeHasher> BlockHashesTree<H> {
+    pub fn hash_leaf(block_hash: [u8; 32]) -> [u8; 32] {
+        let mut bytes = [0u8; 33];
+        bytes[0] = LEAF_TAG;
+        bytes[1..].copy_from_slice(&block_hash);
+        H::hash(&bytes)
+    }
+
+    pub fn hash_nodes(left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
+        let mut bytes = [0u8; 65];
+        bytes[0] = NODE_TAG;
+        bytes[1..33].copy_from_slice(&left);
+        bytes[33..].copy_from_slice(&right);
+        H::hash(&bytes)
+    }
+
+    pub fn hash_root(leaf_count: u64, tree_root: [u8; 32]) -> [u8; 32] {
+        let mut bytes = [0u8; 41];
+        bytes[0] = ROOT_TAG;
+        bytes[1..9].copy_from_slice(&leaf_count.to_be_bytes());
+        bytes[9..].copy_from_slice(&tree_root);
+        H::hash(&bytes)
+    }
+
+    fn next_level(current_level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+        current_level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => Self::hash_nodes(*left, *right),
+                // promote the last node of an odd level
+                [last] => *last,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    pub fn root(block_hashes: &[[u8; 32]]) -> [u8; 32] {
+        let mut current_level = block_hashes
+            .iter()
+            .map(|block_hash| Self::hash_leaf(*block_hash))
+            .collect::<Vec<_>>();
+        while current_level.len() > 1 {
+            current_level = Self::next_level(&current_level);
+        }
+        Self::hash_root(
+            block_hashes.len() as u64,
+            current_level.first().copied().unwrap_or([0u8; 32]),
+        )
+    }
+
+    // siblings of the leaf at `index`, from the bottom up, promoted levels have no sibling
+    pub fn branch(block_hashes: &[[u8; 32]], index: usize) -> Vec<[u8; 32]> {
+        let mut branch = vec![];
+        let mut index = index;
+        let mut current_level = block_hashes
+            .iter()
+            .map(|block_hash| Self::hash_leaf(*block_hash))
+            .collect::<Vec<_>>();
+        while current_level.len() > 1 {
+            let sibling = index ^ 1;
+            if sibling < current_level.len() {
+                branch.push(current_level[sibling]);
+            }
+            current_level = Self::next_level(&current_level);
+            index /= 2;
+        }
+        branch
+    }
+
+    pub fn verify_branch(
+        root: [u8; 32],
+        block_hash: [u8; 32],
+        index: u64,
+        leaf_count: u64,
+        branch: &[[u8; 32]],
+    ) -> bool {
+        if index >= leaf_count {
+            return false;
+        }
+
+        let mut node = Self::hash_leaf(block_hash);
+        let mut index = index;
+        let mut level_width = leaf_count;
+        let mut siblings = branch.iter();
+        while level_width > 1 {
+            // the last node of an odd level is promoted without a sibling
+            if index != level_width - 1 || level_width % 2 == 0 {
+                let Some(sibling) = siblings.next() else {
+                    return false;
+                };
+                node = if index % 2 == 0 {
+                    Self::hash_nodes(node, *sibling)
+                } else {
+                    Self::hash_nodes(*sibling, node)
+                };
+            }
+            index /= 2;
+            level_width = level_width.div_ceil(2);
+        }
+
+        siblings.next().is_none() && Self::hash_root(leaf_count, node) == root
+    }
+}
+
+// Hash function of the block hashes commitment, committed in the public values
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CommitmentScheme {
+    #[default]
+    Sha256 = 0,
+    // cheaper to verify on the EVM
+    Keccak256 = 1,
+}
+
+impl TryFrom<u8> for CommitmentScheme {
+    type Error = alloy_sol_types::Error;
+
+    fn try_from(scheme: u8) -> Result<Self, Self::Error> {
+        match scheme {
+            0 => Ok(CommitmentScheme::Sha256),
+            1 => Ok(CommitmentScheme::Keccak256),
+            _ => Err(alloy_sol_types::Error::custom("Unknown commitment scheme")),
+        }
+    }
+}
+
+impl CommitmentScheme {
+    pub fn hash(&self, bytes: &[u8]) -> [u8; 32] {
+        match self {
+            CommitmentScheme::Sha256 => sha256_hash(bytes),
+            CommitmentScheme::Keccak256 => keccak256_hash(bytes),
+        }
+    }
+
+    pub fn block_hashes_root(&self, block_hashes: &[[u8; 32]]) -> [u8; 32] {
+        match self {
+            CommitmentScheme::Sha256 => Sha256BlockHashes::root(block_hashes),
+            CommitmentScheme::Keccak256 => Keccak256BlockHashes::root(block_hashes),
+        }
+    }
+
+    pub fn block_hashes_branch(&self, block_hashes: &[[u8; 32]], index: usize) -> Vec<[u8; 32]> {
+        match self {
+            CommitmentScheme::Sha256 => Sha256BlockHashes::branch(block_hashes, index),
+            CommitmentScheme::Keccak256 => Keccak256BlockHashes::branch(block_hashes, index),
+        }
+    }
+
+    pub fn verify_block_hashes_branch(
+        &self,
+        root: [u8; 32],
+        block_hash: [u8; 32],
+        index: u64,
+        leaf_count: u64,
+        branch: &[[u8; 32]],
+    ) -> bool {
+        match self {
+            CommitmentScheme::Sha256 => {
+                Sha256BlockHashes::verify_branch(root, block_hash, index, leaf_count, branch)
+            }
+            CommitmentScheme::Keccak256 => {
+                Keccak256BlockHashes::verify_branch(root, block_hash, index, leaf_count, branch)
+            }
+        }
+    }
+}
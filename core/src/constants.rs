@@ -2,3 +2,20 @@ pub const MAX_BLOCKS: usize = 500;
 pub const EPOCH_BLOCK_NUMBER: u32 = 2016;
 pub const BLOCK_TIMEVAL: u32 = 600;
 pub const EXPECTED_EPOCH_SECONDS: u32 = EPOCH_BLOCK_NUMBER * BLOCK_TIMEVAL;
+pub const MEDIAN_TIME_SPAN: usize = 11;
+// on networks allowing min-difficulty blocks, a block this long after its parent may be mined at
+// the PoW limit
+pub const MIN_DIFFICULTY_BLOCK_SPACING: u32 = 2 * BLOCK_TIMEVAL;
+// FlyClient mode: number of sampled headers, and number of headers verified in full at the tip.
+// Samples fall in B equally likely buckets, B being about log2 of the header count (see
+// `flyclient_sample_point`). A fork of the last 1 / 2^k of the sampled work, held by an adversary
+// with a fraction c of the hash power, escapes a sample with probability at most
+// (k + (B - k) * c) / B, and every sample with that probability to the power `FLYCLIENT_SAMPLES`:
+// with 850k headers (B = 20) and c = 1/2, about 2^-119 for k = 1, 2^-53 for k = 10 and 2^-25 for
+// k = 15. Forks within the last `FLYCLIENT_SUFFIX_BLOCKS` headers are verified in full instead.
+pub const FLYCLIENT_SAMPLES: usize = 128;
+pub const FLYCLIENT_SUFFIX_BLOCKS: u64 = 100;
+// version of the circuit rules, committed in the public values, bumped on every rule change
+pub const PROGRAM_VERSION: u32 = 1;
+// rule flags committed in the public values, one bit per rule enabled at compile time
+pub const RULE_CHECKPOINTS: u32 = 1 << 0;
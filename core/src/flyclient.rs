@@ -0,0 +1,382 @@
+use crate::btc_light_client::{
+    assert_safe_block, assert_target_bits, block_work, verify_block, AsLittleEndianBytes, Block,
+    EpochState,
+};
+use crate::commitment::CommitmentScheme;
+use crate::constants::{
+    EPOCH_BLOCK_NUMBER, FLYCLIENT_SAMPLES, FLYCLIENT_SUFFIX_BLOCKS, MEDIAN_TIME_SPAN,
+};
+use crate::genesis::Network;
+use crate::mmr::{add_work, WorkMmrProof, WorkNode};
+use crate::sha256_merkle::sha256_hash;
+use crate::{assert_anchoring, Anchor, CircuitPublicValues, VerificationMode};
+
+use crypto_bigint::{Encoding, U256};
+use serde::{Deserialize, Serialize};
+
+// FlyClient (https://eprint.iacr.org/2019/226) probabilistic verification of a header chain.
+//
+// The prover commits to every header from the safe block to the tip in a difficulty-aware MMR (see
+// `WorkNode`), so that every header owns the interval of the work of the chain before it, as
+// derived from its inclusion proof. Work points are then sampled with Fiat-Shamir randomness taken
+// from the tip and the MMR root, and the header owning each point is opened and verified in full
+// with `verify_block`, on top of the committed headers before it: an adversary with a fraction c of
+// the claimed work survives a sample with probability c. The last headers are verified in full the
+// same way. The MMR root is committed in place of `block_hashes_merkle_root`, and the tip epoch
+// state is committed as zero.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlyClientHeader {
+    pub block: Block,
+    pub proof: WorkMmrProof,
+}
+
+// Consecutive committed headers, verified in full on top of the state of the chain before them
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FlyClientRun {
+    // start of the epoch of the parent of the first header, unless it is the epoch of the safe block
+    pub epoch_start: Option<FlyClientHeader>,
+    // the `MEDIAN_TIME_SPAN` headers before the first one, unless it follows the safe block
+    pub context: Vec<FlyClientHeader>,
+    pub headers: Vec<FlyClientHeader>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlyClientInput {
+    pub public_values: CircuitPublicValues,
+    pub safe_block: FlyClientHeader,
+    pub epoch_state: EpochState,
+    pub anchor: Anchor,
+    pub network: Network,
+    // headers committed in the MMR, from the safe block to the tip
+    pub leaf_count: u64,
+    // last headers of the chain, the last one is the tip
+    pub suffix: FlyClientRun,
+    // the last header of each run is the sampled one
+    pub samples: Vec<FlyClientRun>,
+}
+
+// Fiat-Shamir seed, the MMR root must be bound so that it can't be ground for a given tip
+pub fn flyclient_seed(tip_block_hash: [u8; 32], mmr_root: [u8; 32], leaf_count: u64) -> [u8; 32] {
+    let mut bytes = [0u8; 72];
+    bytes[0..32].copy_from_slice(&tip_block_hash);
+    bytes[32..64].copy_from_slice(&mmr_root);
+    bytes[64..72].copy_from_slice(&leaf_count.to_be_bytes());
+    sha256_hash(&bytes)
+}
+
+// Work point of the i-th sample in [region_start, region_end). FlyClient samples with a density
+// growing as 1 / (1 - x) towards the tip, approximated here by buckets halving the remaining
+// distance to the end of the region: bucket j covers [W - W / 2^j, W - W / 2^(j+1)) and all buckets
+// are equally likely. Integer only, so that the host predicts the exact same points.
+pub fn flyclient_sample_point(
+    seed: [u8; 32],
+    index: usize,
+    region_start: U256,
+    region_end: U256,
+    leaf_count: u64,
+) -> U256 {
+    let mut bytes = [0u8; 36];
+    bytes[0..32].copy_from_slice(&seed);
+    bytes[32..36].copy_from_slice(&(index as u32).to_be_bytes());
+    let randomness = sha256_hash(&bytes);
+
+    let width = region_end.wrapping_sub(&region_start);
+    // buckets go down to roughly a single block, and never get empty
+    let bucket_count = (64 - leaf_count.leading_zeros())
+        .min(width.bits() as u32)
+        .max(1);
+    let bucket =
+        (u64::from_be_bytes(randomness[24..32].try_into().unwrap()) % bucket_count as u64) as usize;
+    let bucket_start = width.wrapping_sub(&(width >> bucket));
+    let bucket_end = width.wrapping_sub(&(width >> (bucket + 1)));
+    let mut offset_bytes = [0u8; 32];
+    offset_bytes[8..].copy_from_slice(&randomness[0..24]);
+    let offset =
+        U256::from_be_bytes(offset_bytes).wrapping_rem(&bucket_end.wrapping_sub(&bucket_start));
+
+    region_start
+        .wrapping_add(&bucket_start)
+        .wrapping_add(&offset)
+}
+
+// checks the header is committed as the leaf of its height, returns the work of the leaves before it
+fn assert_committed_header(
+    header: &FlyClientHeader,
+    block_hash: [u8; 32],
+    mmr_root: [u8; 32],
+    safe_block_height: u64,
+    leaf_count: u64,
+) -> U256 {
+    assert_eq!(
+        header.proof.leaf_index + safe_block_height,
+        header.block.height,
+        "FlyClient leaf index does not match block height"
+    );
+    assert_eq!(
+        header.proof.leaf_count, leaf_count,
+        "FlyClient leaf count mismatch"
+    );
+    header
+        .proof
+        .verify(
+            mmr_root,
+            &WorkNode::leaf(
+                block_hash,
+                header.block.height,
+                block_work(header.block.bits),
+            ),
+        )
+        .expect("FlyClient header is not committed")
+}
+
+// verifies the headers of a run in full, returns the work of the leaves before its first and last
+// headers, and the hash of its last header
+fn assert_flyclient_run(run: &FlyClientRun, input: &FlyClientInput) -> (U256, U256, [u8; 32]) {
+    let public_values = &input.public_values;
+    let safe_block_height = public_values.safe_block_height;
+    let assert_committed = |header: &FlyClientHeader, block_hash: [u8; 32]| {
+        assert_committed_header(
+            header,
+            block_hash,
+            public_values.block_hashes_merkle_root,
+            safe_block_height,
+            input.leaf_count,
+        )
+    };
+    assert!(!run.headers.is_empty(), "FlyClient run is empty");
+    let first_height = run.headers[0].block.height;
+
+    // [1] the state before the first header is the state of the safe block, or is rebuilt from the
+    // committed headers before it and the start of their epoch
+    let (mut epoch_state, mut previous_block, mut previous_block_hash) =
+        if first_height == safe_block_height + 1 {
+            assert!(
+                run.context.is_empty() && run.epoch_start.is_none(),
+                "FlyClient run on top of the safe block has a context"
+            );
+            (
+                input.epoch_state,
+                input.safe_block.block,
+                public_values.safe_block_hash,
+            )
+        } else {
+            assert_eq!(
+                run.context.len(),
+                MEDIAN_TIME_SPAN,
+                "FlyClient run context length mismatch"
+            );
+            let mut timestamps = [[0u8; 4]; MEDIAN_TIME_SPAN];
+            let mut previous_block_hash = None;
+            for (i, header) in run.context.iter().enumerate() {
+                assert_eq!(
+                    header.block.height + (MEDIAN_TIME_SPAN - i) as u64,
+                    first_height,
+                    "FlyClient context height mismatch"
+                );
+                let block_hash = header.block.compute_block_hash();
+                assert_committed(header, block_hash);
+                if let Some(previous_block_hash) = previous_block_hash {
+                    assert_eq!(
+                        header.block.prev_blockhash.to_little_endian(),
+                        previous_block_hash,
+                        "FlyClient context is not linked"
+                    );
+                }
+                previous_block_hash = Some(block_hash);
+                timestamps[i] = header.block.time;
+            }
+
+            let parent_height = first_height - 1;
+            let epoch_start_height = parent_height - parent_height % EPOCH_BLOCK_NUMBER as u64;
+            let (epoch_start_time, bits) = match &run.epoch_start {
+                None => {
+                    assert_eq!(
+                        input.epoch_state.epoch_start_height, epoch_start_height,
+                        "FlyClient run is not in the epoch of the safe block"
+                    );
+                    (input.epoch_state.epoch_start_time, input.epoch_state.bits)
+                }
+                Some(epoch_start) => {
+                    assert!(
+                        epoch_start_height > safe_block_height
+                            && epoch_start.block.height == epoch_start_height,
+                        "FlyClient epoch start height mismatch"
+                    );
+                    assert_committed(epoch_start, epoch_start.block.compute_block_hash());
+                    (epoch_start.block.time, epoch_start.block.bits)
+                }
+            };
+            // the chainwork is taken from the MMR instead
+            let epoch_state = EpochState {
+                epoch_start_height,
+                epoch_start_time,
+                bits,
+                chainwork: [0u8; 32],
+                timestamps,
+            };
+            (
+                epoch_state,
+                run.context[MEDIAN_TIME_SPAN - 1].block,
+                previous_block_hash.unwrap(),
+            )
+        };
+
+    // [2] verify every header as in `assert_blockchain`, and its commitment
+    let mut work_before = vec![];
+    for header in run.headers.iter() {
+        if header.block.height % EPOCH_BLOCK_NUMBER as u64 == 0 {
+            assert_target_bits(&epoch_state, &previous_block, &header.block, input.network);
+            epoch_state.begin_epoch(&header.block);
+        }
+        previous_block_hash = verify_block(
+            previous_block_hash,
+            &header.block,
+            &epoch_state,
+            previous_block.height,
+            input.network,
+        );
+        epoch_state.accumulate(&header.block);
+        previous_block = header.block;
+        work_before.push(assert_committed(header, previous_block_hash));
+    }
+
+    (
+        work_before[0],
+        *work_before.last().unwrap(),
+        previous_block_hash,
+    )
+}
+
+pub fn validate_flyclient(input: FlyClientInput) -> CircuitPublicValues {
+    let public_values = &input.public_values;
+    let mmr_root = public_values.block_hashes_merkle_root;
+    let safe_block_height = public_values.safe_block_height;
+    assert_eq!(
+        public_values.verification_mode,
+        VerificationMode::FlyClient,
+        "Verification mode mismatch"
+    );
+    assert!(
+        input.leaf_count >= 2,
+        "FlyClient chain has no block on top of the safe block"
+    );
+
+    // [1] the safe block is checked as in the full mode, and is the first leaf
+    assert_eq!(
+        input.epoch_state.compute_state_hash(),
+        public_values.epoch_state_hash,
+        "Initial epoch state hash mismatch"
+    );
+    assert_safe_block(
+        public_values.safe_block_hash,
+        safe_block_height,
+        &input.safe_block.block,
+        &input.epoch_state,
+        input.network,
+    );
+    assert_anchoring(
+        public_values,
+        &input.safe_block.block,
+        &input.epoch_state,
+        &input.anchor,
+        input.network,
+    );
+    assert_committed_header(
+        &input.safe_block,
+        public_values.safe_block_hash,
+        mmr_root,
+        safe_block_height,
+        input.leaf_count,
+    );
+    let safe_block_work = block_work(input.safe_block.block.bits);
+
+    // [2] the suffix is verified in full up to the tip, the last leaf
+    let tip_height = safe_block_height + input.leaf_count - 1;
+    let (suffix_work_before, tip_work_before, tip_block_hash) =
+        assert_flyclient_run(&input.suffix, &input);
+    let suffix_start = input.suffix.headers[0].block.height;
+    let tip = input.suffix.headers.last().unwrap();
+    assert!(
+        input.suffix.headers.len() as u64 >= FLYCLIENT_SUFFIX_BLOCKS.min(input.leaf_count - 1),
+        "FlyClient suffix is too short"
+    );
+    assert_eq!(
+        tip.block.height, tip_height,
+        "FlyClient tip is not the last leaf"
+    );
+
+    // [3] sample the work between the safe block and the suffix
+    let region_start = safe_block_work;
+    let region_end = suffix_work_before;
+    assert!(
+        region_start <= region_end,
+        "FlyClient chainwork is not increasing"
+    );
+    let sample_count = if region_start == region_end {
+        0
+    } else {
+        FLYCLIENT_SAMPLES
+    };
+    assert_eq!(
+        input.samples.len(),
+        sample_count,
+        "FlyClient sample count mismatch"
+    );
+    let seed = flyclient_seed(tip_block_hash, mmr_root, input.leaf_count);
+    for (i, sample) in input.samples.iter().enumerate() {
+        let point = flyclient_sample_point(seed, i, region_start, region_end, input.leaf_count);
+        let (_, sample_work_before, _) = assert_flyclient_run(sample, &input);
+        let sampled_block = &sample.headers.last().unwrap().block;
+        assert!(
+            sampled_block.height < suffix_start,
+            "FlyClient sample is outside of the sampled region"
+        );
+        assert!(
+            sample_work_before <= point
+                && point < add_work(sample_work_before, block_work(sampled_block.bits)),
+            "FlyClient sample does not own the sampled work"
+        );
+    }
+
+    // [4] the chainwork of the tip follows from the work committed in the MMR
+    let mmr_work = tip.proof.total_work();
+    assert_eq!(
+        add_work(tip_work_before, block_work(tip.block.bits)),
+        mmr_work,
+        "FlyClient tip is not the last leaf"
+    );
+    let tip_chainwork = add_work(
+        U256::from_be_slice(&input.epoch_state.chainwork),
+        mmr_work.wrapping_sub(&safe_block_work),
+    );
+
+    // [5] check committed tip
+    assert_eq!(
+        public_values.tip_block_hash, tip_block_hash,
+        "Tip block hash mismatch"
+    );
+    assert_eq!(
+        public_values.tip_block_height, tip_height,
+        "Tip block height mismatch"
+    );
+    assert_eq!(
+        public_values.tip_chainwork,
+        tip_chainwork.to_be_bytes(),
+        "Tip chainwork mismatch"
+    );
+    assert_eq!(
+        public_values.tip_epoch_state_hash, [0u8; 32],
+        "FlyClient proofs have no tip epoch state"
+    );
+    assert_eq!(
+        public_values.commitment_scheme,
+        CommitmentScheme::Sha256,
+        "FlyClient proofs commit a sha256 MMR"
+    );
+    assert!(
+        public_values.prev_mmr_root == [0u8; 32] && public_values.mmr_root == [0u8; 32],
+        "FlyClient proofs don't extend the block hashes MMR"
+    );
+
+    input.public_values
+}
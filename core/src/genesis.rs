@@ -0,0 +1,96 @@
+use crate::btc_light_client::{Block, EpochState};
+use crate::CircuitPublicValues;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Network {
+    #[default]
+    Bitcoin,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+// merkle root of the genesis coinbase, shared by all networks (internal byte order)
+const GENESIS_MERKLE_ROOT: [u8; 32] = [
+    0x3b, 0xa3, 0xed, 0xfd, 0x7a, 0x7b, 0x12, 0xb2, 0x7a, 0xc7, 0x2c, 0x3e, 0x67, 0x76, 0x8f, 0x61,
+    0x7f, 0xc8, 0x1b, 0xc3, 0x88, 0x8a, 0x51, 0x32, 0x3a, 0x9f, 0xb8, 0xaa, 0x4b, 0x1e, 0x5e, 0x4a,
+];
+
+impl Network {
+    // message start bytes of the P2P protocol, committed in the public values to tell networks apart
+    pub fn magic(&self) -> [u8; 4] {
+        match self {
+            Network::Bitcoin => [0xf9, 0xbe, 0xb4, 0xd9],
+            Network::Testnet => [0x0b, 0x11, 0x09, 0x07],
+            Network::Signet => [0x0a, 0x03, 0xcf, 0x40],
+            Network::Regtest => [0xfa, 0xbf, 0xb5, 0xda],
+        }
+    }
+
+    // easiest allowed target, every header's target must be at most this one
+    pub fn pow_limit_bits(&self) -> [u8; 4] {
+        match self {
+            Network::Bitcoin | Network::Testnet => 0x1d00ffffu32,
+            Network::Signet => 0x1e0377ae,
+            Network::Regtest => 0x207fffff,
+        }
+        .to_le_bytes()
+    }
+
+    // regtest never adjusts the difficulty, the target of the last block is kept at a retarget
+    pub fn retargets(&self) -> bool {
+        !matches!(self, Network::Regtest)
+    }
+
+    // testnet and regtest accept a block at the PoW limit when it comes more than
+    // `MIN_DIFFICULTY_BLOCK_SPACING` after its parent, outside of retarget heights
+    pub fn allows_min_difficulty_blocks(&self) -> bool {
+        matches!(self, Network::Testnet | Network::Regtest)
+    }
+
+    pub fn genesis_block(&self) -> Block {
+        let (time, bits, nonce) = match self {
+            Network::Bitcoin => (1231006505u32, 0x1d00ffffu32, 2083236893u32),
+            Network::Testnet => (1296688602, 0x1d00ffff, 414098458),
+            Network::Signet => (1598918400, 0x1e0377ae, 52613770),
+            Network::Regtest => (1296688602, 0x207fffff, 2),
+        };
+
+        Block {
+            height: 0,
+            version: 1u32.to_le_bytes(),
+            prev_blockhash: [0u8; 32],
+            merkle_root: GENESIS_MERKLE_ROOT,
+            time: time.to_le_bytes(),
+            bits: bits.to_le_bytes(),
+            nonce: nonce.to_le_bytes(),
+        }
+    }
+
+    // the genesis block opens the first epoch, and has no predecessor to take timestamps from
+    pub fn genesis_epoch_state(&self) -> EpochState {
+        let genesis_block = self.genesis_block();
+        EpochState::from_anchor(&genesis_block, genesis_block.time)
+    }
+}
+
+// A chain anchored at height 0 is anchored on the hardcoded genesis header of the network, so
+// that neither the safe block nor its epoch state are trusted.
+pub fn assert_genesis(
+    public_values: &CircuitPublicValues,
+    epoch_state: &EpochState,
+    network: Network,
+) {
+    assert_eq!(
+        public_values.safe_block_hash,
+        network.genesis_block().compute_block_hash(),
+        "Safe block is not the genesis block"
+    );
+    assert_eq!(
+        *epoch_state,
+        network.genesis_epoch_state(),
+        "Epoch state is not the genesis epoch state"
+    );
+}
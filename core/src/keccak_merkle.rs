@@ -0,0 +1,22 @@
+use crate::commitment::{BlockHashesTree, TreeHasher};
+use tiny_keccak::{Hasher, Keccak};
+
+pub fn keccak256_hash(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(bytes);
+    let mut hash = [0u8; 32];
+    hasher.finalize(&mut hash);
+    hash
+}
+
+pub struct Keccak256Hasher;
+
+impl TreeHasher for Keccak256Hasher {
+    fn hash(bytes: &[u8]) -> [u8; 32] {
+        keccak256_hash(bytes)
+    }
+}
+
+// Same tree as `Sha256BlockHashes` with keccak256, so that a contract checks an inclusion branch
+// with `keccak256(abi.encodePacked(bytes1(0x01), left, right))` per level.
+pub type Keccak256BlockHashes = BlockHashesTree<Keccak256Hasher>;
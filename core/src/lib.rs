@@ -1,10 +1,40 @@
+// SP1 cycle tracker sections, accumulated by name in the execution report. Only printed in the
+// zkVM, where the executor intercepts them, and when the crate calling them is built with its
+// `profiling` feature: the prints cost cycles and change the program, hence its vkey.
+#[macro_export]
+macro_rules! cycle_tracker_start {
+    ($name:literal) => {
+        #[cfg(all(target_os = "zkvm", feature = "profiling"))]
+        println!(concat!("cycle-tracker-report-start: ", $name));
+    };
+}
+
+#[macro_export]
+macro_rules! cycle_tracker_end {
+    ($name:literal) => {
+        #[cfg(all(target_os = "zkvm", feature = "profiling"))]
+        println!(concat!("cycle-tracker-report-end: ", $name));
+    };
+}
+
+pub mod aggregation;
+pub mod block_contents;
 pub mod btc_light_client;
+#[cfg(feature = "checkpoints")]
+pub mod checkpoints;
+pub mod commitment;
 pub mod constants;
+pub mod flyclient;
+pub mod genesis;
+pub mod keccak_merkle;
+pub mod mmr;
 pub mod sha256_merkle;
+pub mod transaction;
 
-use constants::{MAX_BLOCKS};
-use alloy_sol_types::sol;
-// use crypto_bigint::U256;
+use alloy_sol_types::private::{FixedBytes, U256};
+use alloy_sol_types::{sol, SolType};
+use commitment::CommitmentScheme;
+use constants::MAX_BLOCKS;
 use serde::{Deserialize, Serialize};
 
 mod arrays {
@@ -66,43 +96,219 @@ mod arrays {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Copy)]
+#[derive(Serialize, Deserialize, Clone, Debug, Copy, Default)]
 pub struct CircuitPublicValues {
-    pub retarget_block_hash: [u8; 32],
+    pub epoch_state_hash: [u8; 32],
+    pub safe_block_hash: [u8; 32],
     pub safe_block_height: u64,
+    pub tip_block_hash: [u8; 32],
+    pub tip_block_height: u64,
+    pub tip_epoch_state_hash: [u8; 32],
+    pub tip_chainwork: [u8; 32],
     pub block_hashes_merkle_root: [u8; 32],
+    pub commitment_scheme: CommitmentScheme,
+    pub prev_mmr_root: [u8; 32],
+    pub mmr_root: [u8; 32],
+    pub checkpoints_root: [u8; 32],
+    pub anchor_vkey_digest: [u8; 32],
+    pub verification_mode: VerificationMode,
+    pub domain_tag: DomainTag,
 }
 
 sol! {
     struct ProofPublicInputs {
-        bytes32 retarget_block_hash;
+        bytes32 epoch_state_hash;
+        bytes32 safe_block_hash;
         uint64 safe_block_height;
+        bytes32 tip_block_hash;
+        uint64 tip_block_height;
+        bytes32 tip_epoch_state_hash;
+        uint256 tip_chainwork;
         bytes32 block_hashes_merkle_root;
-    }
-}
-
-impl Default for CircuitPublicValues {
-    fn default() -> Self {
-        CircuitPublicValues {
-            retarget_block_hash: [0u8; 32],
-            safe_block_height: 0,
-            block_hashes_merkle_root: [0u8; 32],
-        }
+        uint8 commitment_scheme;
+        bytes32 prev_mmr_root;
+        bytes32 mmr_root;
+        bytes32 checkpoints_root;
+        bytes32 anchor_vkey_digest;
+        uint8 verification_mode;
+        bytes4 network_magic;
+        uint32 program_version;
+        uint32 rule_flags;
     }
 }
 
 impl CircuitPublicValues {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        retarget_block_hash: [u8; 32],
+        epoch_state_hash: [u8; 32],
+        safe_block_hash: [u8; 32],
         safe_block_height: u64,
+        tip_block_hash: [u8; 32],
+        tip_block_height: u64,
+        tip_epoch_state_hash: [u8; 32],
+        tip_chainwork: [u8; 32],
         block_hashes_merkle_root: [u8; 32],
+        commitment_scheme: CommitmentScheme,
+        prev_mmr_root: [u8; 32],
+        mmr_root: [u8; 32],
+        checkpoints_root: [u8; 32],
+        anchor_vkey_digest: [u8; 32],
+        verification_mode: VerificationMode,
+        domain_tag: DomainTag,
     ) -> Self {
         Self {
-            retarget_block_hash,
+            epoch_state_hash,
+            safe_block_hash,
             safe_block_height,
+            tip_block_hash,
+            tip_block_height,
+            tip_epoch_state_hash,
+            tip_chainwork,
             block_hashes_merkle_root,
+            commitment_scheme,
+            prev_mmr_root,
+            mmr_root,
+            checkpoints_root,
+            anchor_vkey_digest,
+            verification_mode,
+            domain_tag,
+        }
+    }
+
+    pub fn abi_encode(&self) -> Vec<u8> {
+        ProofPublicInputs::abi_encode(&self.abi_public_inputs())
+    }
+
+    pub fn abi_public_inputs(&self) -> ProofPublicInputs {
+        ProofPublicInputs {
+            epoch_state_hash: FixedBytes::from(self.epoch_state_hash),
+            safe_block_hash: FixedBytes::from(self.safe_block_hash),
+            safe_block_height: self.safe_block_height,
+            tip_block_hash: FixedBytes::from(self.tip_block_hash),
+            tip_block_height: self.tip_block_height,
+            tip_epoch_state_hash: FixedBytes::from(self.tip_epoch_state_hash),
+            tip_chainwork: U256::from_be_bytes(self.tip_chainwork),
+            block_hashes_merkle_root: FixedBytes::from(self.block_hashes_merkle_root),
+            commitment_scheme: self.commitment_scheme as u8,
+            prev_mmr_root: FixedBytes::from(self.prev_mmr_root),
+            mmr_root: FixedBytes::from(self.mmr_root),
+            checkpoints_root: FixedBytes::from(self.checkpoints_root),
+            anchor_vkey_digest: FixedBytes::from(self.anchor_vkey_digest),
+            verification_mode: self.verification_mode as u8,
+            network_magic: FixedBytes::from(self.domain_tag.network_magic),
+            program_version: self.domain_tag.program_version,
+            rule_flags: self.domain_tag.rule_flags,
         }
     }
+
+    // fails on malformed bytes and on unknown commitment schemes or verification modes
+    pub fn abi_decode(bytes: &[u8]) -> Result<Self, alloy_sol_types::Error> {
+        Self::from_abi_public_inputs(ProofPublicInputs::abi_decode(bytes, true)?)
+    }
+
+    // fails on unknown commitment schemes or verification modes
+    pub fn from_abi_public_inputs(
+        public_inputs: ProofPublicInputs,
+    ) -> Result<Self, alloy_sol_types::Error> {
+        Ok(Self {
+            epoch_state_hash: public_inputs.epoch_state_hash.0,
+            safe_block_hash: public_inputs.safe_block_hash.0,
+            safe_block_height: public_inputs.safe_block_height,
+            tip_block_hash: public_inputs.tip_block_hash.0,
+            tip_block_height: public_inputs.tip_block_height,
+            tip_epoch_state_hash: public_inputs.tip_epoch_state_hash.0,
+            tip_chainwork: public_inputs.tip_chainwork.to_be_bytes(),
+            block_hashes_merkle_root: public_inputs.block_hashes_merkle_root.0,
+            commitment_scheme: public_inputs.commitment_scheme.try_into()?,
+            prev_mmr_root: public_inputs.prev_mmr_root.0,
+            mmr_root: public_inputs.mmr_root.0,
+            checkpoints_root: public_inputs.checkpoints_root.0,
+            anchor_vkey_digest: public_inputs.anchor_vkey_digest.0,
+            verification_mode: public_inputs.verification_mode.try_into()?,
+            domain_tag: DomainTag {
+                network_magic: public_inputs.network_magic.0,
+                program_version: public_inputs.program_version,
+                rule_flags: public_inputs.rule_flags,
+            },
+        })
+    }
+}
+
+// Network and rule set a proof was made for, so that a proof of another network or of another
+// version of the circuit can't be replayed against a verifier sharing the vkey
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DomainTag {
+    pub network_magic: [u8; 4],
+    pub program_version: u32,
+    pub rule_flags: u32,
+}
+
+impl DomainTag {
+    // domain of the proofs of `network` made by this build of the program
+    pub fn new(network: genesis::Network) -> Self {
+        let mut rule_flags = 0;
+        if cfg!(feature = "checkpoints") {
+            rule_flags |= constants::RULE_CHECKPOINTS;
+        }
+        Self {
+            network_magic: network.magic(),
+            program_version: constants::PROGRAM_VERSION,
+            rule_flags,
+        }
+    }
+}
+
+// How the header chain of a proof was verified, committed in the public values
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum VerificationMode {
+    // every header is verified, with the difficulty adjustment
+    #[default]
+    Full = 0,
+    // the headers are sampled, see `flyclient`
+    FlyClient = 1,
+}
+
+impl TryFrom<u8> for VerificationMode {
+    type Error = alloy_sol_types::Error;
+
+    fn try_from(mode: u8) -> Result<Self, Self::Error> {
+        match mode {
+            0 => Ok(VerificationMode::Full),
+            1 => Ok(VerificationMode::FlyClient),
+            _ => Err(alloy_sol_types::Error::custom("Unknown verification mode")),
+        }
+    }
+}
+
+// What the chain of a proof is anchored on, only enforced with the `checkpoints` feature
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub enum Anchor {
+    // the safe block is one of the compiled-in checkpoints
+    #[default]
+    Checkpoint,
+    // the safe block is the tip of a previous proof of this program, which is verified recursively.
+    // `vkey_digest` is given by the prover and committed, the verifier checks it is this program's
+    ProvenTip {
+        vkey_digest: [u32; 8],
+        public_values: CircuitPublicValues,
+    },
+    // the safe block is the tip of the previous chunk of an aggregated range, the proof commits no
+    // anchor and is only meaningful once aggregated, see `aggregation`
+    Chained,
+}
+
+// SP1 vkey digest as committed in the public values. A program can't know its own vkey, so the
+// digest of a proof it verifies recursively is given by the prover and committed: the verifier of
+// the outer proof must check it is the digest of the expected program, otherwise the outer proof
+// builds on whatever another program proved.
+pub fn vkey_digest_to_bytes(vkey_digest: &[u32; 8]) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(vkey_digest) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    bytes
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -111,14 +317,20 @@ pub struct CircuitInput {
     #[serde(with = "arrays")]
     pub blocks: [btc_light_client::Block; MAX_BLOCKS],
     pub utilized_blocks: u64,
-    pub retarget_block: btc_light_client::Block,
+    pub epoch_state: btc_light_client::EpochState,
+    pub mmr_peaks: mmr::MmrPeaks,
+    pub anchor: Anchor,
+    pub network: genesis::Network,
 }
 
 impl CircuitInput {
     pub fn new(
         public_values: CircuitPublicValues,
         blocks: Vec<btc_light_client::Block>,
-        retarget_block: btc_light_client::Block,
+        epoch_state: btc_light_client::EpochState,
+        mmr_peaks: mmr::MmrPeaks,
+        anchor: Anchor,
+        network: genesis::Network,
     ) -> Self {
         let mut padded_blocks = [btc_light_client::Block::default(); MAX_BLOCKS];
         for (i, block) in blocks.iter().enumerate() {
@@ -129,7 +341,10 @@ impl CircuitInput {
             public_values,
             blocks: padded_blocks,
             utilized_blocks: blocks.len() as u64,
-            retarget_block,
+            epoch_state,
+            mmr_peaks,
+            anchor,
+            network,
         }
     }
 }
@@ -140,21 +355,112 @@ impl Default for CircuitInput {
             public_values: CircuitPublicValues::default(),
             blocks: [btc_light_client::Block::default(); MAX_BLOCKS],
             utilized_blocks: 0,
-            retarget_block: btc_light_client::Block::default(),
+            epoch_state: btc_light_client::EpochState::default(),
+            mmr_peaks: mmr::MmrPeaks::default(),
+            anchor: Anchor::default(),
+            network: genesis::Network::default(),
         }
     }
 }
 
 pub fn validate_block(circuit_input: CircuitInput) -> CircuitPublicValues {
-    let blocks = circuit_input.blocks[0..(circuit_input.utilized_blocks as usize)].to_vec();
+    let blocks = &circuit_input.blocks[0..(circuit_input.utilized_blocks as usize)];
+    assert_eq!(
+        circuit_input.public_values.verification_mode,
+        VerificationMode::Full,
+        "Verification mode mismatch"
+    );
     // Block Verification
-    btc_light_client::assert_blockchain(
-        circuit_input.public_values.block_hashes_merkle_root,
-        circuit_input.public_values.safe_block_height,
-        circuit_input.public_values.retarget_block_hash,
+    let block_hashes = btc_light_client::assert_blockchain(
+        &circuit_input.public_values,
         blocks,
-        circuit_input.retarget_block,
+        circuit_input.epoch_state,
+        circuit_input.network,
+    );
+
+    // MMR Verification
+    cycle_tracker_start!("mmr");
+    mmr::assert_mmr(
+        circuit_input.public_values.prev_mmr_root,
+        circuit_input.public_values.mmr_root,
+        &circuit_input.mmr_peaks,
+        &block_hashes,
+    );
+    cycle_tracker_end!("mmr");
+
+    assert_anchoring(
+        &circuit_input.public_values,
+        &blocks[0],
+        &circuit_input.epoch_state,
+        &circuit_input.anchor,
+        circuit_input.network,
     );
 
     circuit_input.public_values
 }
+
+// Genesis and anchor checks of the safe block, shared by all verification modes
+pub(crate) fn assert_anchoring(
+    public_values: &CircuitPublicValues,
+    safe_block: &btc_light_client::Block,
+    epoch_state: &btc_light_client::EpochState,
+    anchor: &Anchor,
+    network: genesis::Network,
+) {
+    // Domain Verification
+    assert_eq!(
+        public_values.domain_tag,
+        DomainTag::new(network),
+        "Domain tag mismatch"
+    );
+
+    // Genesis Verification
+    if public_values.safe_block_height == 0 {
+        genesis::assert_genesis(public_values, epoch_state, network);
+    }
+
+    // Anchor Verification
+    #[cfg(feature = "checkpoints")]
+    checkpoints::assert_anchor(public_values, safe_block, epoch_state, anchor);
+    #[cfg(not(feature = "checkpoints"))]
+    {
+        let _ = (safe_block, anchor);
+        assert!(
+            public_values.checkpoints_root == [0u8; 32]
+                && public_values.anchor_vkey_digest == [0u8; 32],
+            "Checkpoints are not enabled"
+        );
+    }
+}
+
+// Input of the program, one per verification mode
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ProgramInput {
+    Full(CircuitInput),
+    FlyClient(flyclient::FlyClientInput),
+    BlockContents(block_contents::BlockContentsInput),
+}
+
+impl ProgramInput {
+    pub fn anchor(&self) -> Anchor {
+        match self {
+            ProgramInput::Full(circuit_input) => circuit_input.anchor,
+            ProgramInput::FlyClient(flyclient_input) => flyclient_input.anchor,
+            ProgramInput::BlockContents(contents_input) => contents_input.circuit_input.anchor,
+        }
+    }
+}
+
+// ABI-encoded public values of the program
+pub fn validate(program_input: ProgramInput) -> Vec<u8> {
+    match program_input {
+        ProgramInput::Full(circuit_input) => validate_block(circuit_input).abi_encode(),
+        ProgramInput::FlyClient(flyclient_input) => {
+            flyclient::validate_flyclient(flyclient_input).abi_encode()
+        }
+        ProgramInput::BlockContents(contents_input) => {
+            block_contents::validate_block_contents(contents_input)
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use crate::sha256_merkle::{sha256_hash, Sha256BlockHashes};
+
+use crypto_bigint::{CheckedAdd, Encoding, U256};
+use serde::{Deserialize, Serialize};
+
+// a u64 leaf count has at most one peak per bit
+pub const MMR_MAX_PEAKS: usize = 64;
+const MMR_ROOT_TAG: u8 = 0x03;
+const WORK_LEAF_TAG: u8 = 0x04;
+const WORK_NODE_TAG: u8 = 0x05;
+const WORK_ROOT_TAG: u8 = 0x06;
+
+// Merkle Mountain Range of every proven block hash, in height order. Leaves and inner nodes are
+// hashed as in `Sha256BlockHashes`, only the peaks are carried from one proof to the next.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct MmrPeaks {
+    pub leaf_count: u64,
+    // peaks from the highest to the lowest, only the first `leaf_count.count_ones()` are used
+    #[serde(with = "crate::arrays")]
+    pub peaks: [[u8; 32]; MMR_MAX_PEAKS],
+}
+
+impl Default for MmrPeaks {
+    fn default() -> Self {
+        Self {
+            leaf_count: 0,
+            peaks: [[0u8; 32]; MMR_MAX_PEAKS],
+        }
+    }
+}
+
+impl MmrPeaks {
+    pub fn from_peaks(leaf_count: u64, peaks: &[[u8; 32]]) -> Self {
+        assert_eq!(
+            peaks.len(),
+            leaf_count.count_ones() as usize,
+            "Peak count does not match leaf count"
+        );
+        let mut mmr_peaks = Self {
+            leaf_count,
+            ..Default::default()
+        };
+        mmr_peaks.peaks[..peaks.len()].copy_from_slice(peaks);
+        mmr_peaks
+    }
+
+    pub fn peaks(&self) -> &[[u8; 32]] {
+        &self.peaks[..self.leaf_count.count_ones() as usize]
+    }
+
+    pub fn append(&mut self, block_hash: [u8; 32]) {
+        self.append_leaf(Sha256BlockHashes::hash_leaf(block_hash));
+    }
+
+    // appends an already hashed leaf node
+    pub fn append_leaf(&mut self, leaf: [u8; 32]) {
+        let mut node = leaf;
+        let mut peak_count = self.leaf_count.count_ones() as usize;
+        // every trailing one of the leaf count is a peak of the same height to merge with
+        for _ in 0..self.leaf_count.trailing_ones() {
+            peak_count -= 1;
+            node = Sha256BlockHashes::hash_nodes(self.peaks[peak_count], node);
+        }
+        self.peaks[peak_count] = node;
+        self.leaf_count += 1;
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        mmr_root(self.leaf_count, self.peaks())
+    }
+}
+
+pub fn mmr_root(leaf_count: u64, peaks: &[[u8; 32]]) -> [u8; 32] {
+    let mut bytes = Vec::with_capacity(9 + 32 * peaks.len());
+    bytes.push(MMR_ROOT_TAG);
+    bytes.extend_from_slice(&leaf_count.to_be_bytes());
+    for peak in peaks {
+        bytes.extend_from_slice(peak);
+    }
+    sha256_hash(&bytes)
+}
+
+// (first leaf, position, height) of the peak covering the leaf at `leaf_index`, peaks are ordered
+// by the bits of the leaf count
+pub fn leaf_peak(leaf_index: u64, leaf_count: u64) -> (u64, usize, usize) {
+    let mut peak_start = 0u64;
+    let mut peak_position = 0;
+    for height in (0..64).rev() {
+        if leaf_count & (1 << height) == 0 {
+            continue;
+        }
+        if leaf_index < peak_start + (1 << height) {
+            return (peak_start, peak_position, height);
+        }
+        peak_start += 1 << height;
+        peak_position += 1;
+    }
+    (peak_start, peak_position, 0)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MmrProof {
+    pub leaf_index: u64,
+    pub leaf_count: u64,
+    // siblings from the leaf up to its peak
+    pub siblings: Vec<[u8; 32]>,
+    pub peaks: Vec<[u8; 32]>,
+}
+
+impl MmrProof {
+    pub fn verify(&self, root: [u8; 32], block_hash: [u8; 32]) -> bool {
+        self.verify_leaf(root, Sha256BlockHashes::hash_leaf(block_hash))
+    }
+
+    // verifies the inclusion of an already hashed leaf node
+    pub fn verify_leaf(&self, root: [u8; 32], leaf: [u8; 32]) -> bool {
+        if self.leaf_index >= self.leaf_count
+            || self.peaks.len() != self.leaf_count.count_ones() as usize
+        {
+            return false;
+        }
+
+        // [1] find the peak covering the leaf
+        let (peak_start, peak_position, peak_height) = leaf_peak(self.leaf_index, self.leaf_count);
+        if self.siblings.len() != peak_height {
+            return false;
+        }
+
+        // [2] climb up to the peak
+        let mut node = leaf;
+        let mut index = self.leaf_index - peak_start;
+        for sibling in self.siblings.iter() {
+            node = if index % 2 == 0 {
+                Sha256BlockHashes::hash_nodes(node, *sibling)
+            } else {
+                Sha256BlockHashes::hash_nodes(*sibling, node)
+            };
+            index /= 2;
+        }
+
+        // [3] check the peak and the root
+        node == self.peaks[peak_position] && mmr_root(self.leaf_count, &self.peaks) == root
+    }
+}
+
+// Appends the block hashes proven on top of the safe block to the MMR built up to the safe block.
+// An empty MMR starts with the safe block itself.
+pub fn assert_mmr(
+    prev_mmr_root: [u8; 32],
+    mmr_root: [u8; 32],
+    prev_mmr_peaks: &MmrPeaks,
+    block_hashes: &[[u8; 32]],
+) {
+    assert_eq!(
+        prev_mmr_peaks.root(),
+        prev_mmr_root,
+        "Previous MMR root mismatch"
+    );
+
+    let mut mmr_peaks = *prev_mmr_peaks;
+    let skip = if mmr_peaks.leaf_count == 0 { 0 } else { 1 };
+    for block_hash in block_hashes.iter().skip(skip) {
+        mmr_peaks.append(*block_hash);
+    }
+
+    assert_eq!(mmr_peaks.root(), mmr_root, "MMR root mismatch");
+}
+
+// Difficulty-aware MMR of the FlyClient mode: every node commits the work of its subtree next to
+// its hash, so that the work of the chain before a leaf follows from the leaf inclusion proof, and
+// the work of the whole chain from the peaks. Leaves bind the block hash to its height.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WorkNode {
+    pub hash: [u8; 32],
+    // work of the subtree (big-endian)
+    pub work: [u8; 32],
+}
+
+pub fn add_work(work: U256, other_work: U256) -> U256 {
+    Option::from(work.checked_add(&other_work)).expect("Work overflow")
+}
+
+impl WorkNode {
+    pub fn leaf(block_hash: [u8; 32], height: u64, work: U256) -> Self {
+        let work = work.to_be_bytes();
+        let mut bytes = [0u8; 73];
+        bytes[0] = WORK_LEAF_TAG;
+        bytes[1..33].copy_from_slice(&block_hash);
+        bytes[33..41].copy_from_slice(&height.to_be_bytes());
+        bytes[41..].copy_from_slice(&work);
+        Self {
+            hash: sha256_hash(&bytes),
+            work,
+        }
+    }
+
+    pub fn parent(left: &WorkNode, right: &WorkNode) -> Self {
+        let work = add_work(left.work(), right.work()).to_be_bytes();
+        let mut bytes = [0u8; 97];
+        bytes[0] = WORK_NODE_TAG;
+        bytes[1..33].copy_from_slice(&left.hash);
+        bytes[33..65].copy_from_slice(&right.hash);
+        bytes[65..].copy_from_slice(&work);
+        Self {
+            hash: sha256_hash(&bytes),
+            work,
+        }
+    }
+
+    pub fn work(&self) -> U256 {
+        U256::from_be_bytes(self.work)
+    }
+}
+
+pub fn work_mmr_root(leaf_count: u64, peaks: &[WorkNode]) -> [u8; 32] {
+    let mut bytes = Vec::with_capacity(9 + 64 * peaks.len());
+    bytes.push(WORK_ROOT_TAG);
+    bytes.extend_from_slice(&leaf_count.to_be_bytes());
+    for peak in peaks {
+        bytes.extend_from_slice(&peak.hash);
+        bytes.extend_from_slice(&peak.work);
+    }
+    sha256_hash(&bytes)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WorkMmrProof {
+    pub leaf_index: u64,
+    pub leaf_count: u64,
+    // siblings from the leaf up to its peak
+    pub siblings: Vec<WorkNode>,
+    pub peaks: Vec<WorkNode>,
+}
+
+impl WorkMmrProof {
+    // verifies the inclusion of `leaf`, returns the work of the leaves before it
+    pub fn verify(&self, root: [u8; 32], leaf: &WorkNode) -> Option<U256> {
+        if self.leaf_index >= self.leaf_count
+            || self.peaks.len() != self.leaf_count.count_ones() as usize
+        {
+            return None;
+        }
+
+        // [1] find the peak covering the leaf, the peaks before it are all before the leaf
+        let (peak_start, peak_position, peak_height) = leaf_peak(self.leaf_index, self.leaf_count);
+        if self.siblings.len() != peak_height {
+            return None;
+        }
+        let mut work_before = self.peaks[..peak_position]
+            .iter()
+            .fold(U256::ZERO, |work, peak| add_work(work, peak.work()));
+
+        // [2] climb up to the peak, left siblings are before the leaf
+        let mut node = *leaf;
+        let mut index = self.leaf_index - peak_start;
+        for sibling in self.siblings.iter() {
+            node = if index % 2 == 0 {
+                WorkNode::parent(&node, sibling)
+            } else {
+                work_before = add_work(work_before, sibling.work());
+                WorkNode::parent(sibling, &node)
+            };
+            index /= 2;
+        }
+
+        // [3] check the peak and the root
+        (node == self.peaks[peak_position] && work_mmr_root(self.leaf_count, &self.peaks) == root)
+            .then_some(work_before)
+    }
+
+    // work of every leaf, once the proof is verified
+    pub fn total_work(&self) -> U256 {
+        self.peaks
+            .iter()
+            .fold(U256::ZERO, |work, peak| add_work(work, peak.work()))
+    }
+}
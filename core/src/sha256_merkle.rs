@@ -1,28 +1,34 @@
 use crate::{btc_light_client::AsLittleEndianBytes};
+use crate::commitment::{BlockHashesTree, TreeHasher};
 // use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+// in the zkVM, the patched sha2 crate runs the compression function with the SHA-256 precompile
 pub fn sha256_hash(bytes: &[u8]) -> [u8; 32] {
     Sha256::digest(bytes).into()
 }
 
+pub fn double_sha256(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(Sha256::digest(bytes)).into()
+}
+
 pub fn hash_pairs(hash_1: [u8; 32], hash_2: [u8; 32]) -> [u8; 32] {
     // [0] & [1] Combine hashes into one 64 byte array, reversing byte order
-    let combined_hashes: [u8; 64] = hash_1
-        .into_iter()
-        .rev()
-        .chain(hash_2.into_iter().rev())
-        .collect::<Vec<u8>>()
-        .try_into()
-        .unwrap();
+    let mut combined_hashes = [0u8; 64];
+    for i in 0..32 {
+        combined_hashes[i] = hash_1[31 - i];
+        combined_hashes[32 + i] = hash_2[31 - i];
+    }
 
     // [2] Double sha256 combined hashes
-    let new_hash_be = sha256_hash(&sha256_hash(&combined_hashes));
+    let new_hash_be = double_sha256(&combined_hashes);
 
     // [3] Convert new hash to little-endian
     new_hash_be.to_little_endian()
 }
 
+// Bitcoin transaction merkle root, only meant for txids as the last node of an odd level is
+// duplicated, see `Sha256BlockHashes` for block hashes
 pub fn get_merkle_root(leaves: Vec<[u8; 32]>) -> [u8; 32] {
     let mut current_level = leaves;
     while current_level.len() > 1 {
@@ -46,3 +52,30 @@ pub fn get_merkle_root(leaves: Vec<[u8; 32]>) -> [u8; 32] {
     }
     current_level[0]
 }
+
+// Bitcoin transaction merkle root from the txid at `index` and its branch, from the bottom up
+pub fn get_merkle_root_from_branch(txid: [u8; 32], index: u32, branch: &[[u8; 32]]) -> [u8; 32] {
+    assert!(
+        branch.len() < 32 && index >> branch.len() == 0,
+        "Transaction index out of the merkle branch"
+    );
+    let mut node = txid;
+    for (level, sibling) in branch.iter().enumerate() {
+        node = if (index >> level) & 1 == 0 {
+            hash_pairs(node, *sibling)
+        } else {
+            hash_pairs(*sibling, node)
+        };
+    }
+    node
+}
+
+pub struct Sha256Hasher;
+
+impl TreeHasher for Sha256Hasher {
+    fn hash(bytes: &[u8]) -> [u8; 32] {
+        sha256_hash(bytes)
+    }
+}
+
+pub type Sha256BlockHashes = BlockHashesTree<Sha256Hasher>;
@@ -0,0 +1,226 @@
+use crate::btc_light_client::AsLittleEndianBytes;
+use crate::sha256_merkle::double_sha256;
+
+use sha2::{Digest, Sha256};
+
+// Reads the consensus serialization of blocks and transactions, panicking on malformed data
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pub position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    pub fn peek(&self) -> u8 {
+        assert!(self.position < self.bytes.len(), "Unexpected end of data");
+        self.bytes[self.position]
+    }
+
+    pub fn read(&mut self, length: usize) -> &'a [u8] {
+        assert!(
+            length <= self.bytes.len() - self.position,
+            "Unexpected end of data"
+        );
+        let bytes = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        bytes
+    }
+
+    pub fn read_array<const N: usize>(&mut self) -> [u8; N] {
+        self.read(N).try_into().unwrap()
+    }
+
+    pub fn read_u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.read_array())
+    }
+
+    pub fn read_u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.read_array())
+    }
+
+    // CompactSize, which must be minimally encoded
+    pub fn read_compact_size(&mut self) -> u64 {
+        let (value, minimum) = match self.read(1)[0] {
+            0xfd => (u16::from_le_bytes(self.read_array()) as u64, 0xfd),
+            0xfe => (u32::from_le_bytes(self.read_array()) as u64, 0x10000),
+            0xff => (self.read_u64(), 0x100000000),
+            value => (value as u64, 0),
+        };
+        assert!(value >= minimum, "Non-canonical CompactSize");
+        value
+    }
+
+    pub fn read_var_bytes(&mut self) -> &'a [u8] {
+        let length = self.read_compact_size();
+        self.read(length as usize)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TxIn<'a> {
+    // previous output, txid in RPC byte order
+    pub prev_txid: [u8; 32],
+    pub prev_vout: u32,
+    pub script_sig: &'a [u8],
+    pub sequence: u32,
+}
+
+impl TxIn<'_> {
+    pub fn is_coinbase(&self) -> bool {
+        self.prev_txid == [0u8; 32] && self.prev_vout == u32::MAX
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TxOut<'a> {
+    pub value: u64,
+    pub script_pubkey: &'a [u8],
+}
+
+// Parsed transaction, keeping the offsets of its parts in its serialization
+#[derive(Clone, Copy, Debug)]
+pub struct Transaction<'a> {
+    pub bytes: &'a [u8],
+    pub input_count: u64,
+    pub output_count: u64,
+    // offsets of the inputs, outputs, witnesses and lock time
+    inputs_start: usize,
+    outputs_start: usize,
+    witnesses_start: usize,
+    lock_time_start: usize,
+}
+
+impl<'a> Transaction<'a> {
+    // parses the transaction at the position of the reader
+    pub fn read(reader: &mut Reader<'a>) -> Self {
+        let start = reader.position;
+        reader.read(4);
+
+        // [1] segwit transactions have a 0x00 marker and a 0x01 flag in place of the input count
+        let segwit = reader.peek() == 0;
+        if segwit {
+            reader.read(1);
+            assert_eq!(reader.read(1)[0], 1, "Unknown transaction flag");
+        }
+
+        // [2] inputs: outpoint, script sig, sequence
+        let inputs_start = reader.position - start;
+        let input_count = reader.read_compact_size();
+        for _ in 0..input_count {
+            reader.read(36);
+            reader.read_var_bytes();
+            reader.read(4);
+        }
+
+        // [3] outputs: value, script pubkey
+        let outputs_start = reader.position - start;
+        let output_count = reader.read_compact_size();
+        for _ in 0..output_count {
+            reader.read(8);
+            reader.read_var_bytes();
+        }
+
+        // [4] one witness stack per input
+        let witnesses_start = reader.position - start;
+        if segwit {
+            assert!(input_count > 0, "Segwit transaction without inputs");
+            let mut has_witness = false;
+            for _ in 0..input_count {
+                let item_count = reader.read_compact_size();
+                for _ in 0..item_count {
+                    reader.read_var_bytes();
+                }
+                has_witness |= item_count > 0;
+            }
+            assert!(has_witness, "Superfluous segwit serialization");
+        }
+
+        let lock_time_start = reader.position - start;
+        reader.read(4);
+
+        Self {
+            bytes: &reader.bytes[start..reader.position],
+            input_count,
+            output_count,
+            inputs_start,
+            outputs_start,
+            witnesses_start,
+            lock_time_start,
+        }
+    }
+
+    pub fn parse(bytes: &'a [u8]) -> Self {
+        let mut reader = Reader::new(bytes);
+        let transaction = Self::read(&mut reader);
+        assert!(reader.is_empty(), "Trailing data after transaction");
+        transaction
+    }
+
+    pub fn is_segwit(&self) -> bool {
+        self.witnesses_start != self.lock_time_start
+    }
+
+    pub fn inputs(&self) -> Vec<TxIn<'a>> {
+        let mut reader = Reader::new(&self.bytes[self.inputs_start..self.outputs_start]);
+        reader.read_compact_size();
+        (0..self.input_count)
+            .map(|_| TxIn {
+                prev_txid: reader.read_array::<32>().to_little_endian(),
+                prev_vout: reader.read_u32(),
+                script_sig: reader.read_var_bytes(),
+                sequence: reader.read_u32(),
+            })
+            .collect()
+    }
+
+    pub fn outputs(&self) -> Vec<TxOut<'a>> {
+        let mut reader = Reader::new(&self.bytes[self.outputs_start..self.witnesses_start]);
+        reader.read_compact_size();
+        (0..self.output_count)
+            .map(|_| TxOut {
+                value: reader.read_u64(),
+                script_pubkey: reader.read_var_bytes(),
+            })
+            .collect()
+    }
+
+    // witness stack of every input, empty for non-segwit transactions
+    pub fn witnesses(&self) -> Vec<Vec<&'a [u8]>> {
+        if !self.is_segwit() {
+            return vec![vec![]; self.input_count as usize];
+        }
+        let mut reader = Reader::new(&self.bytes[self.witnesses_start..self.lock_time_start]);
+        (0..self.input_count)
+            .map(|_| {
+                let item_count = reader.read_compact_size();
+                (0..item_count).map(|_| reader.read_var_bytes()).collect()
+            })
+            .collect()
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.input_count == 1 && self.inputs()[0].is_coinbase()
+    }
+
+    // double sha256 of the serialization without witness data, in RPC byte order
+    pub fn txid(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.bytes[0..4]);
+        hasher.update(&self.bytes[self.inputs_start..self.witnesses_start]);
+        hasher.update(&self.bytes[self.lock_time_start..]);
+        let txid: [u8; 32] = Sha256::digest(hasher.finalize()).into();
+        txid.to_little_endian()
+    }
+
+    // double sha256 of the entire serialization, in RPC byte order
+    pub fn wtxid(&self) -> [u8; 32] {
+        double_sha256(self.bytes).to_little_endian()
+    }
+}
@@ -0,0 +1,104 @@
+use crate::artifact::ProofSystem;
+use crate::proof::{check_chain, PublicValuesError};
+use zk_light_client_core::aggregation::{
+    AggregatedChunk, AggregationInput, AggregationPublicValues,
+};
+use zk_light_client_core::genesis::Network;
+use zk_light_client_core::{vkey_digest_to_bytes, CircuitInput, CircuitPublicValues};
+
+use alloy_sol_types::SolType;
+use sp1_sdk::{
+    HashableKey, ProverClient, SP1Proof, SP1ProofWithPublicValues, SP1ProvingKey, SP1Stdin,
+    SP1VerifyingKey,
+};
+
+/// The ELF of the aggregation program, which merges the proofs of consecutive chunks.
+pub const AGGREGATION_ELF: &[u8] = include_bytes!("../../elf/aggregation-elf");
+
+/// The block hashes committed by the proof of `input`, from its safe block to its tip.
+pub fn chunk_block_hashes(input: &CircuitInput) -> Vec<[u8; 32]> {
+    input.blocks[0..input.utilized_blocks as usize]
+        .iter()
+        .map(|block| block.compute_block_hash())
+        .collect()
+}
+
+/// The chunk proven by the proof of `input`, as given to the aggregation program.
+pub fn aggregated_chunk(input: &CircuitInput) -> AggregatedChunk {
+    AggregatedChunk {
+        public_values: input.public_values,
+        block_hashes: chunk_block_hashes(input),
+    }
+}
+
+/// Builds the input aggregating the compressed proofs of `chunks`, consecutive chunks as built by
+/// `proof::build_chunk_inputs` or a single proof. The proofs are written to `stdin` so that the
+/// aggregation program can verify them recursively, `vk` is the verifying key of the main program.
+pub fn build_aggregation_input(
+    stdin: &mut SP1Stdin,
+    chunks: Vec<AggregatedChunk>,
+    proofs: &[SP1ProofWithPublicValues],
+    vk: &SP1VerifyingKey,
+) -> AggregationInput {
+    assert_eq!(
+        chunks.len(),
+        proofs.len(),
+        "Every chunk needs exactly one proof"
+    );
+    for proof in proofs {
+        let SP1Proof::Compressed(reduce_proof) = proof.proof.clone() else {
+            panic!("Chunk proofs must be compressed proofs");
+        };
+        stdin.write_proof(reduce_proof, vk.vk.clone());
+    }
+
+    AggregationInput {
+        chunk_vkey_digest: vk.hash_u32(),
+        chunks,
+    }
+}
+
+/// Aggregates the compressed proofs of `chunks` into a single proof of the aggregation program in
+/// `proof_system`, see `build_aggregation_input`. This is how compressed proofs are wrapped into a
+/// Groth16 or PLONK proof verifiable on-chain by `ZkLightClient.updateAggregated`, through the
+/// public prover API.
+pub fn prove_aggregation(
+    client: &ProverClient,
+    aggregation_pk: &SP1ProvingKey,
+    chunks: Vec<AggregatedChunk>,
+    proofs: &[SP1ProofWithPublicValues],
+    vk: &SP1VerifyingKey,
+    proof_system: ProofSystem,
+) -> SP1ProofWithPublicValues {
+    let mut stdin = SP1Stdin::new();
+    let aggregation_input = build_aggregation_input(&mut stdin, chunks, proofs, vk);
+    stdin.write(&aggregation_input);
+
+    let prove = client.prove(aggregation_pk, stdin);
+    match proof_system {
+        ProofSystem::Compressed => prove.compressed().run(),
+        ProofSystem::Groth16 => prove.groth16().run(),
+        ProofSystem::Plonk => prove.plonk().run(),
+    }
+    .expect("failed to generate aggregation proof")
+}
+
+/// Decodes the public values of an aggregation proof into those of the aggregated chain and the
+/// number of chunks, and runs the checks the aggregation program can't do itself: the committed
+/// chunk vkey digest must be the digest of `vk`, the verifying key of the main program. The chain is
+/// then checked as by `proof::check_public_values`.
+pub fn check_aggregation_public_values(
+    proof: &SP1ProofWithPublicValues,
+    vk: &SP1VerifyingKey,
+    network: Network,
+) -> Result<(CircuitPublicValues, u64), PublicValuesError> {
+    let aggregation_public_values =
+        AggregationPublicValues::abi_decode(proof.public_values.as_slice(), true)?;
+    if aggregation_public_values.chunk_vkey_digest.0 != vkey_digest_to_bytes(&vk.hash_u32()) {
+        return Err(PublicValuesError::ChunkVkeyMismatch);
+    }
+    let public_values =
+        CircuitPublicValues::from_abi_public_inputs(aggregation_public_values.chain)?;
+    check_chain(&public_values, vk, network)?;
+    Ok((public_values, aggregation_public_values.chunk_count))
+}
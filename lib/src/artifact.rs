@@ -0,0 +1,178 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::mmr::BlockHashesMmr;
+use crate::proof::PublicValuesError;
+use crate::to_hex_string;
+use zk_light_client_core::aggregation::AggregatedChunk;
+use zk_light_client_core::btc_light_client::EpochState;
+use zk_light_client_core::CircuitPublicValues;
+
+use bitcoin::hashes::hex::FromHex;
+use sp1_sdk::{HashableKey, SP1Proof, SP1ProofWithPublicValues, SP1VerifyingKey};
+
+/// Proof systems a saved proof can be in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProofSystem {
+    Compressed,
+    Groth16,
+    Plonk,
+}
+
+impl ProofSystem {
+    /// Proof system of `proof`, core proofs are never saved.
+    pub fn of(proof: &SP1ProofWithPublicValues) -> Self {
+        match proof.proof {
+            SP1Proof::Compressed(_) => ProofSystem::Compressed,
+            SP1Proof::Groth16(_) => ProofSystem::Groth16,
+            SP1Proof::Plonk(_) => ProofSystem::Plonk,
+            _ => panic!("Core proofs are not saved"),
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ProofSystem::Compressed => "compressed",
+            ProofSystem::Groth16 => "groth16",
+            ProofSystem::Plonk => "plonk",
+        }
+    }
+}
+
+/// Metadata of the run that generated a proof, saved next to it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProofMetadata {
+    pub proof_system: ProofSystem,
+    // vkey hash of the program that generated the proof
+    pub vkey_hash: String,
+    pub safe_block_height: u64,
+    pub safe_block_hash: String,
+    pub tip_block_height: u64,
+    pub tip_block_hash: String,
+    // ABI-encoded public values of the proof
+    pub public_values: String,
+    // block hashes committed by a proof of the main program, from its safe block to its tip, so
+    // that it can be aggregated. Empty for aggregation proofs
+    pub block_hashes: Vec<String>,
+}
+
+impl ProofMetadata {
+    /// Metadata of `proof`, a proof of the range committed in `public_values` by the program of
+    /// `vk`.
+    pub fn new(
+        public_values: &CircuitPublicValues,
+        block_hashes: &[[u8; 32]],
+        proof: &SP1ProofWithPublicValues,
+        vk: &SP1VerifyingKey,
+    ) -> Self {
+        Self {
+            proof_system: ProofSystem::of(proof),
+            vkey_hash: vk.bytes32(),
+            safe_block_height: public_values.safe_block_height,
+            safe_block_hash: to_hex_string(&public_values.safe_block_hash),
+            tip_block_height: public_values.tip_block_height,
+            tip_block_hash: to_hex_string(&public_values.tip_block_hash),
+            public_values: to_hex_string(proof.public_values.as_slice()),
+            block_hashes: block_hashes
+                .iter()
+                .map(|block_hash| to_hex_string(block_hash))
+                .collect(),
+        }
+    }
+
+    /// The chunk proven by `proof`, a proof of the main program saved with this metadata, as given
+    /// to the aggregation program.
+    pub fn aggregated_chunk(
+        &self,
+        proof: &SP1ProofWithPublicValues,
+    ) -> Result<AggregatedChunk, PublicValuesError> {
+        Ok(AggregatedChunk {
+            public_values: CircuitPublicValues::abi_decode(proof.public_values.as_slice())?,
+            block_hashes: self
+                .block_hashes
+                .iter()
+                .map(|block_hash| {
+                    <[u8; 32]>::from_hex(block_hash).expect("Invalid block hash in proof metadata")
+                })
+                .collect(),
+        })
+    }
+}
+
+// `<dir>/<name>.<system>.bin` and `<dir>/<name>.<system>.json`
+fn artifact_paths(dir: &Path, name: &str, proof_system: ProofSystem) -> (PathBuf, PathBuf) {
+    let stem = format!("{name}.{}", proof_system.extension());
+    (
+        dir.join(format!("{stem}.bin")),
+        dir.join(format!("{stem}.json")),
+    )
+}
+
+/// Saves a proof and its metadata to `dir`, returns the path of the proof.
+pub fn save_proof(
+    dir: &Path,
+    name: &str,
+    proof: &SP1ProofWithPublicValues,
+    metadata: &ProofMetadata,
+) -> PathBuf {
+    std::fs::create_dir_all(dir).expect("Failed to create proofs directory");
+    let (proof_path, metadata_path) = artifact_paths(dir, name, metadata.proof_system);
+    proof.save(&proof_path).expect("Failed to save proof");
+    std::fs::write(
+        &metadata_path,
+        serde_json::to_string_pretty(metadata).unwrap(),
+    )
+    .expect("Failed to save proof metadata");
+    proof_path
+}
+
+/// Loads a proof saved by `save_proof` and its metadata, returns them with the proof name.
+pub fn load_proof(proof_path: &Path) -> (SP1ProofWithPublicValues, ProofMetadata, String) {
+    let proof = SP1ProofWithPublicValues::load(proof_path).expect("Failed to load proof");
+    let metadata: ProofMetadata = serde_json::from_str(
+        &std::fs::read_to_string(proof_path.with_extension("json"))
+            .expect("Failed to read proof metadata"),
+    )
+    .expect("Failed to parse proof metadata");
+    assert_eq!(
+        to_hex_string(proof.public_values.as_slice()),
+        metadata.public_values,
+        "Proof metadata doesn't match the proof public values"
+    );
+
+    // strip `.<system>.bin`
+    let file_name = proof_path.file_name().unwrap().to_str().unwrap();
+    let name = file_name
+        .strip_suffix(&format!("{}.bin", metadata.proof_system.extension()))
+        .and_then(|name| name.strip_suffix('.'))
+        .unwrap_or(file_name)
+        .to_string();
+    (proof, metadata, name)
+}
+
+/// Host state at the tip of a proof, from which the next range is proven on top of it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TipState {
+    pub epoch_state: EpochState,
+    pub mmr: BlockHashesMmr,
+}
+
+/// Saves the tip state of the proof `name` to `<dir>/<name>.tip.json`.
+pub fn save_tip_state(dir: &Path, name: &str, tip_state: &TipState) -> PathBuf {
+    std::fs::create_dir_all(dir).expect("Failed to create proofs directory");
+    let path = dir.join(format!("{name}.tip.json"));
+    std::fs::write(&path, serde_json::to_string(tip_state).unwrap())
+        .expect("Failed to save tip state");
+    path
+}
+
+/// Loads the tip state saved by `save_tip_state` next to the proof `name`.
+pub fn load_tip_state(dir: &Path, name: &str) -> TipState {
+    serde_json::from_str(
+        &std::fs::read_to_string(dir.join(format!("{name}.tip.json")))
+            .expect("Failed to read tip state"),
+    )
+    .expect("Failed to parse tip state")
+}
@@ -0,0 +1,178 @@
+use bitcoin::consensus::encode::serialize;
+
+use bitcoin::hashes::Hash;
+
+use crate::proof::{check_chain, PublicValuesError};
+use zk_light_client_core::block_contents::{
+    BlockContentsInput, BlockContentsPublicValues, FullBlock, OpReturnProof, PaymentProof,
+    PreimageProof, SpendProof, TransactionProof, WitnessProof, BLOCK_CONTENTS_PROOF_KIND,
+};
+use zk_light_client_core::btc_light_client::AsLittleEndianBytes;
+use zk_light_client_core::genesis::Network;
+use zk_light_client_core::sha256_merkle::hash_pairs;
+use zk_light_client_core::{CircuitInput, CircuitPublicValues};
+
+use alloy_sol_types::SolType;
+use sp1_sdk::{SP1ProofWithPublicValues, SP1VerifyingKey};
+
+/// Builds the input proving the header chain of `circuit_input` along with claims about the
+/// contents of its blocks.
+///
+/// `full_blocks` are blocks of the chain with their heights, whose transactions are checked
+/// against the header merkle root. The other claims start empty and are filled with
+/// `build_transaction_proof`.
+pub fn build_block_contents_input(
+    circuit_input: CircuitInput,
+    full_blocks: &[(&bitcoin::Block, u64)],
+) -> BlockContentsInput {
+    BlockContentsInput {
+        circuit_input,
+        full_blocks: full_blocks
+            .iter()
+            .map(|(block, height)| FullBlock {
+                height: *height,
+                bytes: serialize(*block),
+            })
+            .collect(),
+        coinbase_heights: vec![],
+        witnesses: vec![],
+        payments: vec![],
+        spends: vec![],
+        preimages: vec![],
+        op_returns: vec![],
+    }
+}
+
+/// Decodes the public values of a block contents proof of the main program whose verifying key is
+/// `vk`. Header chain proofs of the program are rejected, and the chain is checked as by
+/// `proof::check_public_values`.
+pub fn check_block_contents_public_values(
+    proof: &SP1ProofWithPublicValues,
+    vk: &SP1VerifyingKey,
+    network: Network,
+) -> Result<BlockContentsPublicValues, PublicValuesError> {
+    let public_values =
+        BlockContentsPublicValues::abi_decode_params(proof.public_values.as_slice(), true)?;
+    if public_values.proof_kind != BLOCK_CONTENTS_PROOF_KIND {
+        return Err(PublicValuesError::ProofKindMismatch);
+    }
+    check_chain(
+        &CircuitPublicValues::from_abi_public_inputs(public_values.chain.clone())?,
+        vk,
+        network,
+    )?;
+    Ok(public_values)
+}
+
+/// Builds the inclusion proof of the transaction at `index` in `block`, at `height` in the chain.
+/// The coinbase (index 0) proves the height of its block.
+pub fn build_transaction_proof(
+    block: &bitcoin::Block,
+    height: u64,
+    index: u32,
+) -> TransactionProof {
+    let txids = block
+        .txdata
+        .iter()
+        .map(|transaction| {
+            transaction
+                .compute_txid()
+                .to_byte_array()
+                .to_little_endian()
+        })
+        .collect::<Vec<_>>();
+
+    TransactionProof {
+        height,
+        index,
+        transaction: serialize(&block.txdata[index as usize]),
+        branch: merkle_branch(txids, index as usize),
+    }
+}
+
+/// Builds the proof that output `vout` of the transaction at `index` in `block` is paid.
+pub fn build_payment_proof(
+    block: &bitcoin::Block,
+    height: u64,
+    index: u32,
+    vout: u32,
+) -> PaymentProof {
+    PaymentProof {
+        transaction: build_transaction_proof(block, height, index),
+        vout,
+    }
+}
+
+/// Builds the proof that input `vin` of the transaction at `index` in `block` spends its outpoint.
+pub fn build_spend_proof(block: &bitcoin::Block, height: u64, index: u32, vin: u32) -> SpendProof {
+    SpendProof {
+        transaction: build_transaction_proof(block, height, index),
+        vin,
+    }
+}
+
+/// Builds the proof that witness item `item` of input `vin` of the transaction at `index` in
+/// `block` reveals a sha256 preimage.
+pub fn build_preimage_proof(
+    block: &bitcoin::Block,
+    height: u64,
+    index: u32,
+    vin: u32,
+    item: u32,
+) -> PreimageProof {
+    PreimageProof {
+        transaction: build_witness_proof(block, height, index),
+        vin,
+        item,
+    }
+}
+
+/// Builds the proof that OP_RETURN output `vout` of the transaction at `index` in `block` anchors
+/// its payload.
+pub fn build_op_return_proof(
+    block: &bitcoin::Block,
+    height: u64,
+    index: u32,
+    vout: u32,
+) -> OpReturnProof {
+    OpReturnProof {
+        transaction: build_transaction_proof(block, height, index),
+        vout,
+    }
+}
+
+/// Builds the proof that the wtxid of the transaction at `index` in `block` is committed by its
+/// coinbase.
+pub fn build_witness_proof(block: &bitcoin::Block, height: u64, index: u32) -> WitnessProof {
+    // the coinbase wtxid is zero
+    let mut wtxids = vec![[0u8; 32]];
+    wtxids.extend(block.txdata[1..].iter().map(|transaction| {
+        transaction
+            .compute_wtxid()
+            .to_byte_array()
+            .to_little_endian()
+    }));
+
+    WitnessProof {
+        coinbase: build_transaction_proof(block, height, 0),
+        index,
+        transaction: serialize(&block.txdata[index as usize]),
+        witness_branch: merkle_branch(wtxids, index as usize),
+    }
+}
+
+// siblings of the txid (or wtxid) at `index` from the bottom up, in RPC byte order
+fn merkle_branch(leaves: Vec<[u8; 32]>, mut index: usize) -> Vec<[u8; 32]> {
+    let mut branch = vec![];
+    let mut level = leaves;
+    while level.len() > 1 {
+        // the last node of an odd level is paired with itself
+        branch.push(level[(index ^ 1).min(level.len() - 1)]);
+        level = level
+            .chunks(2)
+            .map(|pair| hash_pairs(pair[0], *pair.last().unwrap()))
+            .collect();
+        index /= 2;
+    }
+    branch
+}
@@ -0,0 +1,127 @@
+use crypto_bigint::{Encoding, U256};
+
+use crate::mmr::BlockWorkMmr;
+use crate::proof::anchor_commitments;
+use crate::AsOptimizedBlock;
+use zk_light_client_core::btc_light_client::{block_work, EpochState};
+use zk_light_client_core::commitment::CommitmentScheme;
+use zk_light_client_core::constants::{
+    EPOCH_BLOCK_NUMBER, FLYCLIENT_SAMPLES, FLYCLIENT_SUFFIX_BLOCKS, MEDIAN_TIME_SPAN,
+};
+use zk_light_client_core::flyclient::{
+    flyclient_sample_point, flyclient_seed, FlyClientHeader, FlyClientInput, FlyClientRun,
+};
+use zk_light_client_core::genesis::Network;
+use zk_light_client_core::{Anchor, CircuitPublicValues, DomainTag, VerificationMode};
+
+/// Builds the input proving `blocks` on top of the safe block `blocks[0]` by sampling, see
+/// `zk_light_client_core::flyclient`. Only the `FLYCLIENT_SUFFIX_BLOCKS` tip headers and
+/// `FLYCLIENT_SAMPLES` sampled headers are verified, each with the headers before it giving its
+/// timestamps and target, whatever the length of the chain.
+pub fn build_flyclient_proof_input<T: AsOptimizedBlock>(
+    safe_block_height: u64,
+    blocks: &[T],
+    epoch_state: EpochState,
+    anchor: Anchor,
+    network: Network,
+) -> FlyClientInput {
+    assert!(blocks.len() >= 2, "No block on top of the safe block");
+    let optimized_blocks = blocks
+        .iter()
+        .zip(safe_block_height..safe_block_height + blocks.len() as u64)
+        .map(|(block, height)| block.as_optimized_block(height))
+        .collect::<Vec<_>>();
+
+    // [1] commit every block with its height and work
+    let mut mmr = BlockWorkMmr::new(safe_block_height);
+    let mut work_before = vec![U256::ZERO];
+    for block in optimized_blocks.iter() {
+        mmr.append(block);
+        work_before.push(
+            work_before
+                .last()
+                .unwrap()
+                .wrapping_add(&block_work(block.bits)),
+        );
+    }
+    let header = |index: usize| FlyClientHeader {
+        block: optimized_blocks[index],
+        proof: mmr.prove(safe_block_height + index as u64),
+    };
+    // the run verifying the blocks from `first` to `last`, extended back to the safe block when
+    // there are not enough blocks before it to give its timestamps
+    let run = |first: usize, last: usize| {
+        if first <= MEDIAN_TIME_SPAN {
+            return FlyClientRun {
+                headers: (1..=last).map(header).collect(),
+                ..Default::default()
+            };
+        }
+        let parent_height = safe_block_height + first as u64 - 1;
+        let epoch_start_height = parent_height - parent_height % EPOCH_BLOCK_NUMBER as u64;
+        FlyClientRun {
+            epoch_start: (epoch_start_height > safe_block_height)
+                .then(|| header((epoch_start_height - safe_block_height) as usize)),
+            context: (first - MEDIAN_TIME_SPAN..first).map(header).collect(),
+            headers: (first..=last).map(header).collect(),
+        }
+    };
+
+    // [2] the suffix is verified in full, the work before it is sampled
+    let leaf_count = optimized_blocks.len();
+    let tip_index = leaf_count - 1;
+    let suffix = run(
+        leaf_count - (FLYCLIENT_SUFFIX_BLOCKS as usize).min(tip_index),
+        tip_index,
+    );
+    let suffix_start = (suffix.headers[0].block.height - safe_block_height) as usize;
+    let tip_block = optimized_blocks[tip_index];
+    let tip_block_hash = tip_block.compute_block_hash();
+    let region_start = work_before[1];
+    let region_end = work_before[suffix_start];
+    let samples = if region_start == region_end {
+        vec![]
+    } else {
+        let seed = flyclient_seed(tip_block_hash, mmr.root(), leaf_count as u64);
+        (0..FLYCLIENT_SAMPLES)
+            .map(|i| {
+                let point =
+                    flyclient_sample_point(seed, i, region_start, region_end, leaf_count as u64);
+                // the block owning the point is the last one starting at or before it
+                let index = work_before.partition_point(|work| *work <= point) - 1;
+                run(index, index)
+            })
+            .collect()
+    };
+    let tip_chainwork = U256::from_be_bytes(epoch_state.chainwork)
+        .wrapping_sub(&work_before[1])
+        .wrapping_add(&work_before[leaf_count]);
+    let (checkpoints_root, anchor_vkey_digest) = anchor_commitments(&anchor);
+
+    FlyClientInput {
+        public_values: CircuitPublicValues::new(
+            epoch_state.compute_state_hash(),
+            optimized_blocks[0].compute_block_hash(),
+            safe_block_height,
+            tip_block_hash,
+            tip_block.height,
+            [0u8; 32],
+            tip_chainwork.to_be_bytes(),
+            mmr.root(),
+            CommitmentScheme::Sha256,
+            [0u8; 32],
+            [0u8; 32],
+            checkpoints_root,
+            anchor_vkey_digest,
+            VerificationMode::FlyClient,
+            DomainTag::new(network),
+        ),
+        safe_block: header(0),
+        epoch_state,
+        anchor,
+        network,
+        leaf_count: leaf_count as u64,
+        suffix,
+        samples,
+    }
+}
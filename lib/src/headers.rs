@@ -0,0 +1,211 @@
+use bitcoin::block::Header;
+use bitcoin::consensus::encode::deserialize_partial;
+
+use crate::artifact::TipState;
+use crate::mmr::BlockHashesMmr;
+#[cfg(feature = "checkpoints")]
+use crate::proof::build_checkpoint_epoch_state;
+use crate::proof::{build_block_proof_input, build_chunk_inputs, build_epoch_state};
+use crate::{get_retarget_height_from_block_height, load_hex_bytes};
+use zk_light_client_core::btc_light_client::{block_work, EpochState};
+#[cfg(feature = "checkpoints")]
+use zk_light_client_core::checkpoints::latest_checkpoint;
+use zk_light_client_core::commitment::CommitmentScheme;
+use zk_light_client_core::constants::BLOCK_TIMEVAL;
+use zk_light_client_core::genesis::Network;
+use zk_light_client_core::{Anchor, CircuitInput};
+
+/// Index of the safe block in the headers loaded from `first_block_height`, the blocks before it
+/// only provide the timestamp history of the safe block.
+pub const SAFE_BLOCK_INDEX: usize = 10;
+
+/// Loads the header of `<data_dir>/block_<height>.hex`, only the first 80 bytes are parsed so the
+/// file can hold either a header or a full block.
+pub fn load_header(data_dir: &str, height: u64) -> Header {
+    deserialize_partial::<Header>(&load_hex_bytes(
+        format!("{data_dir}/block_{height}.hex").as_str(),
+    ))
+    .expect("Failed to parse header")
+    .0
+}
+
+/// Loads `num_blocks` headers from the safe block at `first_block_height + SAFE_BLOCK_INDEX`, the
+/// safe block included, and the epoch state at the safe block. The work below the safe block is
+/// not known from the headers, so the chainwork starts with the work of the safe block as for a
+/// chain anchored on it, see `EpochState::from_anchor`.
+pub fn load_chain(
+    data_dir: &str,
+    first_block_height: u64,
+    num_blocks: usize,
+) -> (u64, Vec<Header>, EpochState) {
+    let mined_headers = (first_block_height
+        ..first_block_height + (SAFE_BLOCK_INDEX + num_blocks) as u64)
+        .map(|height| load_header(data_dir, height))
+        .collect::<Vec<_>>();
+
+    let safe_block_height = first_block_height + SAFE_BLOCK_INDEX as u64;
+    let retarget_block_height = get_retarget_height_from_block_height(safe_block_height);
+    let safe_block_bits = mined_headers[SAFE_BLOCK_INDEX]
+        .bits
+        .to_consensus()
+        .to_le_bytes();
+    let epoch_state = build_epoch_state(
+        &load_header(data_dir, retarget_block_height),
+        retarget_block_height,
+        &mined_headers[..=SAFE_BLOCK_INDEX],
+        block_work(safe_block_bits),
+    );
+
+    (
+        safe_block_height,
+        mined_headers[SAFE_BLOCK_INDEX..].to_vec(),
+        epoch_state,
+    )
+}
+
+/// Loads `num_blocks` headers from the compiled-in checkpoint at `checkpoint_height`, the
+/// checkpoint included, and the epoch state of the checkpoint.
+#[cfg(feature = "checkpoints")]
+pub fn load_checkpoint_chain(
+    data_dir: &str,
+    checkpoint_height: u64,
+    num_blocks: usize,
+) -> (u64, Vec<Header>, EpochState) {
+    let headers = (checkpoint_height..checkpoint_height + num_blocks as u64)
+        .map(|height| load_header(data_dir, height))
+        .collect::<Vec<_>>();
+    let epoch_state = build_checkpoint_epoch_state(&headers[0], checkpoint_height);
+    (checkpoint_height, headers, epoch_state)
+}
+
+/// Loads the chain of a range anchored on a checkpoint, as `load_chain` does. With the
+/// `checkpoints` feature the safe block must be a compiled-in checkpoint, so the range starts on the
+/// latest checkpoint at or below the safe block instead, see `load_checkpoint_chain`.
+pub fn load_anchor_chain(
+    data_dir: &str,
+    first_block_height: u64,
+    num_blocks: usize,
+) -> (u64, Vec<Header>, EpochState) {
+    #[cfg(feature = "checkpoints")]
+    {
+        let safe_block_height = first_block_height + SAFE_BLOCK_INDEX as u64;
+        load_checkpoint_chain(
+            data_dir,
+            latest_checkpoint(safe_block_height).height,
+            num_blocks,
+        )
+    }
+    #[cfg(not(feature = "checkpoints"))]
+    {
+        load_chain(data_dir, first_block_height, num_blocks)
+    }
+}
+
+/// Mainnet input proving `num_blocks` blocks, the safe block included, see `load_anchor_chain`.
+pub fn get_circuit_input(
+    data_dir: &str,
+    first_block_height: u64,
+    num_blocks: usize,
+    anchor: Anchor,
+) -> CircuitInput {
+    let (safe_block_height, headers, epoch_state) =
+        load_anchor_chain(data_dir, first_block_height, num_blocks);
+    build_block_proof_input(
+        safe_block_height,
+        &headers,
+        epoch_state,
+        &mut BlockHashesMmr::new(safe_block_height),
+        anchor,
+        Network::Bitcoin,
+        CommitmentScheme::Sha256,
+    )
+}
+
+/// Mainnet input proving `num_blocks` blocks on top of the tip of a previous proof, the tip
+/// included, continuing its epoch state and MMR. `tip_state.mmr` is left at the new tip.
+pub fn get_anchored_circuit_input(
+    data_dir: &str,
+    tip_block_height: u64,
+    num_blocks: usize,
+    tip_state: &mut TipState,
+    anchor: Anchor,
+) -> CircuitInput {
+    let headers = (tip_block_height..tip_block_height + num_blocks as u64)
+        .map(|height| load_header(data_dir, height))
+        .collect::<Vec<_>>();
+    build_block_proof_input(
+        tip_block_height,
+        &headers,
+        tip_state.epoch_state,
+        &mut tip_state.mmr,
+        anchor,
+        Network::Bitcoin,
+        CommitmentScheme::Sha256,
+    )
+}
+
+/// Mainnet inputs of the chunks proving `num_blocks` blocks on top of the safe block, see
+/// `load_anchor_chain` and `proof::build_chunk_inputs`.
+pub fn get_chunk_inputs(
+    data_dir: &str,
+    first_block_height: u64,
+    num_blocks: usize,
+    anchor: Anchor,
+) -> Vec<CircuitInput> {
+    let (safe_block_height, headers, epoch_state) =
+        load_anchor_chain(data_dir, first_block_height, num_blocks + 1);
+    build_chunk_inputs(
+        safe_block_height,
+        &headers,
+        epoch_state,
+        &mut BlockHashesMmr::new(safe_block_height),
+        anchor,
+        Network::Bitcoin,
+        CommitmentScheme::Sha256,
+    )
+}
+
+/// Regtest chain of `num_blocks` headers from the genesis block, mined at the proof of work limit
+/// one block interval apart.
+pub fn mine_regtest_headers(num_blocks: usize) -> Vec<Header> {
+    let mut headers =
+        vec![bitcoin::blockdata::constants::genesis_block(bitcoin::Network::Regtest).header];
+    while headers.len() < num_blocks {
+        let previous = headers.last().unwrap();
+        let mut header = Header {
+            prev_blockhash: previous.block_hash(),
+            time: previous.time + BLOCK_TIMEVAL,
+            nonce: 0,
+            ..*previous
+        };
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        headers.push(header);
+    }
+    headers
+}
+
+/// Regtest input proving `num_blocks` mined blocks, the safe block included, on top of
+/// `SAFE_BLOCK_INDEX` blocks giving its timestamps. The mined blocks are no checkpoint, the chain
+/// is `Anchor::Chained` so that it validates with or without the `checkpoints` feature.
+pub fn get_regtest_circuit_input(num_blocks: usize) -> CircuitInput {
+    let headers = mine_regtest_headers(SAFE_BLOCK_INDEX + num_blocks);
+    let safe_block_height = SAFE_BLOCK_INDEX as u64;
+    let safe_block_bits = headers[SAFE_BLOCK_INDEX].bits.to_consensus().to_le_bytes();
+    let epoch_state = build_epoch_state(
+        &headers[0],
+        0,
+        &headers[..=SAFE_BLOCK_INDEX],
+        block_work(safe_block_bits),
+    );
+    build_block_proof_input(
+        safe_block_height,
+        &headers[SAFE_BLOCK_INDEX..],
+        epoch_state,
+        &mut BlockHashesMmr::new(safe_block_height),
+        Anchor::Chained,
+        Network::Regtest,
+        CommitmentScheme::Sha256,
+    )
+}
@@ -0,0 +1,71 @@
+use std::path::{Path, PathBuf};
+
+use crate::to_hex_string;
+use zk_light_client_core::sha256_merkle::sha256_hash;
+
+use sp1_sdk::{HashableKey, ProverClient, SP1ProvingKey, SP1VerifyingKey};
+
+/// Directory of the key cache, `KEY_CACHE_DIR` or `keys` in the working directory.
+pub fn key_cache_dir() -> PathBuf {
+    std::env::var("KEY_CACHE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("keys"))
+}
+
+// keys are cached per ELF and SP1 version, a rebuilt program or an upgraded prover gets new keys
+fn key_paths(cache_dir: &Path, elf: &[u8], sp1_version: &str) -> (PathBuf, PathBuf) {
+    let elf_hash = to_hex_string(&sha256_hash(elf));
+    (
+        cache_dir.join(format!("{elf_hash}-{sp1_version}.pk")),
+        cache_dir.join(format!("{elf_hash}-{sp1_version}.vk")),
+    )
+}
+
+// writes to a temporary file renamed over `path`, so that an interrupted write never leaves a
+// truncated key behind
+fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    std::fs::write(&tmp_path, bytes)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+fn load_keys(pk_path: &Path, vk_path: &Path) -> Option<(SP1ProvingKey, SP1VerifyingKey)> {
+    let pk = bincode::deserialize(&std::fs::read(pk_path).ok()?).ok()?;
+    let vk = bincode::deserialize(&std::fs::read(vk_path).ok()?).ok()?;
+    Some((pk, vk))
+}
+
+/// `client.setup(elf)`, with the keys persisted to `cache_dir` and reloaded as long as neither the
+/// ELF nor the SP1 version change. Prints the vkey hash that verifier contracts must be pinned to.
+pub fn setup_cached(
+    client: &ProverClient,
+    elf: &[u8],
+    cache_dir: &Path,
+) -> (SP1ProvingKey, SP1VerifyingKey) {
+    let (pk_path, vk_path) = key_paths(cache_dir, elf, client.prover.version());
+    let (pk, vk) = match load_keys(&pk_path, &vk_path) {
+        Some(keys) => {
+            println!("Loaded keys from {}", pk_path.display());
+            keys
+        }
+        // missing or unreadable keys are regenerated
+        None => {
+            let (pk, vk) = client.setup(elf);
+            std::fs::create_dir_all(cache_dir).expect("Failed to create key cache directory");
+            write_atomic(&pk_path, &bincode::serialize(&pk).unwrap())
+                .expect("Failed to write proving key");
+            write_atomic(&vk_path, &bincode::serialize(&vk).unwrap())
+                .expect("Failed to write verifying key");
+            println!("Saved keys to {}", pk_path.display());
+            (pk, vk)
+        }
+    };
+    println!("Program vkey hash: {}", vk.bytes32());
+    (pk, vk)
+}
+
+/// `setup_cached` in the default key cache directory.
+pub fn setup(client: &ProverClient, elf: &[u8]) -> (SP1ProvingKey, SP1VerifyingKey) {
+    setup_cached(client, elf, &key_cache_dir())
+}
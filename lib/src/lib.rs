@@ -1,5 +1,15 @@
+pub mod aggregation;
+pub mod artifact;
+pub mod block_contents;
+pub mod flyclient;
+pub mod headers;
+pub mod keys;
+pub mod mmr;
 pub mod proof;
+pub mod spv;
+pub mod vkey;
 
+use bitcoin::consensus::encode::deserialize;
 use bitcoin::hashes::hex::FromHex;
 use bitcoin::hashes::Hash;
 use std::fmt::Write;
@@ -23,33 +33,58 @@ pub fn get_retarget_height_from_block_height(block_height: u64) -> u64 {
     block_height - (block_height % 2016)
 }
 
+// Heights are not part of a block header, they are always given by the source of the blocks
 pub trait AsOptimizedBlock {
     fn as_optimized_block(&self, height: u64) -> OptimizedBlock;
-    fn as_optimized_block_unsafe(&self) -> OptimizedBlock;
+
+    // little-endian header timestamp, without converting the whole header
+    fn time(&self) -> [u8; 4];
 }
 
-impl AsOptimizedBlock for bitcoin::Block {
+impl AsOptimizedBlock for bitcoin::block::Header {
     fn as_optimized_block(&self, height: u64) -> OptimizedBlock {
         OptimizedBlock {
             height,
-            version: self.header.version.to_consensus().to_le_bytes(),
-            prev_blockhash: self.header.prev_blockhash.to_raw_hash().to_byte_array(),
-            merkle_root: self.header.merkle_root.to_raw_hash().to_byte_array(),
-            time: self.header.time.to_le_bytes(),
-            bits: self.header.bits.to_consensus().to_le_bytes(),
-            nonce: self.header.nonce.to_le_bytes(),
+            version: self.version.to_consensus().to_le_bytes(),
+            prev_blockhash: self.prev_blockhash.to_raw_hash().to_byte_array(),
+            merkle_root: self.merkle_root.to_raw_hash().to_byte_array(),
+            time: self.time.to_le_bytes(),
+            bits: self.bits.to_consensus().to_le_bytes(),
+            nonce: self.nonce.to_le_bytes(),
         }
     }
 
-    fn as_optimized_block_unsafe(&self) -> OptimizedBlock {
-        OptimizedBlock {
-            height: self.bip34_block_height().unwrap(),
-            version: self.header.version.to_consensus().to_le_bytes(),
-            prev_blockhash: self.header.prev_blockhash.to_raw_hash().to_byte_array(),
-            merkle_root: self.header.merkle_root.to_raw_hash().to_byte_array(),
-            time: self.header.time.to_le_bytes(),
-            bits: self.header.bits.to_consensus().to_le_bytes(),
-            nonce: self.header.nonce.to_le_bytes(),
-        }
+    fn time(&self) -> [u8; 4] {
+        self.time.to_le_bytes()
+    }
+}
+
+impl AsOptimizedBlock for bitcoin::Block {
+    fn as_optimized_block(&self, height: u64) -> OptimizedBlock {
+        self.header.as_optimized_block(height)
+    }
+
+    fn time(&self) -> [u8; 4] {
+        self.header.time()
+    }
+}
+
+impl AsOptimizedBlock for [u8; 80] {
+    fn as_optimized_block(&self, height: u64) -> OptimizedBlock {
+        OptimizedBlock::from_bytes(self, height)
+    }
+
+    fn time(&self) -> [u8; 4] {
+        self[68..72].try_into().unwrap()
+    }
+}
+
+pub trait AsBitcoinHeader {
+    fn as_bitcoin_header(&self) -> bitcoin::block::Header;
+}
+
+impl AsBitcoinHeader for OptimizedBlock {
+    fn as_bitcoin_header(&self) -> bitcoin::block::Header {
+        deserialize(&self.serialize()).expect("Failed to deserialize header")
     }
 }
@@ -0,0 +1,154 @@
+use zk_light_client_core::btc_light_client::{block_work, Block as OptimizedBlock};
+use zk_light_client_core::mmr::{
+    mmr_root, work_mmr_root, MmrPeaks, MmrProof, WorkMmrProof, WorkNode,
+};
+use zk_light_client_core::sha256_merkle::Sha256BlockHashes;
+
+use serde::{Deserialize, Serialize};
+
+/// Node of a host-side MMR, merged with its sibling into their parent.
+pub trait MmrNode: Copy {
+    fn parent(left: &Self, right: &Self) -> Self;
+}
+
+impl MmrNode for [u8; 32] {
+    fn parent(left: &Self, right: &Self) -> Self {
+        Sha256BlockHashes::hash_nodes(*left, *right)
+    }
+}
+
+impl MmrNode for WorkNode {
+    fn parent(left: &Self, right: &Self) -> Self {
+        WorkNode::parent(left, right)
+    }
+}
+
+/// Host-side Merkle Mountain Range of consecutive blocks, keeping every node so that an inclusion
+/// proof can be built for any historical height against the latest root. It is serializable so
+/// that it can be kept between proofs.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Mmr<N> {
+    /// Height of the first leaf, i.e. the safe block of the first proof
+    pub first_height: u64,
+    // levels[h] holds the roots of the complete subtrees of height h, left to right
+    levels: Vec<Vec<N>>,
+}
+
+/// MMR of every proven block hash, whose peaks are carried by the program.
+pub type BlockHashesMmr = Mmr<[u8; 32]>;
+
+/// Difficulty-aware MMR of the headers of a FlyClient proof, see
+/// `zk_light_client_core::mmr::WorkNode`.
+pub type BlockWorkMmr = Mmr<WorkNode>;
+
+impl<N: MmrNode> Mmr<N> {
+    pub fn new(first_height: u64) -> Self {
+        Self {
+            first_height,
+            levels: vec![vec![]],
+        }
+    }
+
+    pub fn leaf_count(&self) -> u64 {
+        self.levels[0].len() as u64
+    }
+
+    /// Appends an already hashed leaf node.
+    pub fn append_leaf(&mut self, leaf: N) {
+        self.levels[0].push(leaf);
+        let mut height = 0;
+        // a level with an even number of nodes completes a subtree one level up
+        while self.levels[height].len() % 2 == 0 {
+            let level = &self.levels[height];
+            let node = N::parent(&level[level.len() - 2], &level[level.len() - 1]);
+            if self.levels.len() == height + 1 {
+                self.levels.push(vec![]);
+            }
+            self.levels[height + 1].push(node);
+            height += 1;
+        }
+    }
+
+    /// Peaks from the highest to the lowest, as carried by the program.
+    pub fn peaks(&self) -> Vec<N> {
+        self.levels
+            .iter()
+            .rev()
+            .filter(|level| level.len() % 2 == 1)
+            .map(|level| *level.last().unwrap())
+            .collect()
+    }
+
+    // (leaf index, siblings from the leaf up to its peak) of the block at `height`
+    fn branch(&self, height: u64) -> (u64, Vec<N>) {
+        assert!(
+            height >= self.first_height && height - self.first_height < self.leaf_count(),
+            "Height is not in the MMR"
+        );
+        let leaf_index = height - self.first_height;
+
+        let mut siblings = vec![];
+        let mut index = leaf_index as usize;
+        for level in self.levels.iter() {
+            let sibling = index ^ 1;
+            // the subtree is a peak once its sibling is not complete yet
+            if sibling >= level.len() {
+                break;
+            }
+            siblings.push(level[sibling]);
+            index /= 2;
+        }
+        (leaf_index, siblings)
+    }
+}
+
+impl BlockHashesMmr {
+    pub fn append(&mut self, block_hash: [u8; 32]) {
+        self.append_leaf(Sha256BlockHashes::hash_leaf(block_hash));
+    }
+
+    pub fn mmr_peaks(&self) -> MmrPeaks {
+        MmrPeaks::from_peaks(self.leaf_count(), &self.peaks())
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        mmr_root(self.leaf_count(), &self.peaks())
+    }
+
+    /// Inclusion proof of the block hash at `height` against the current root.
+    pub fn prove(&self, height: u64) -> MmrProof {
+        let (leaf_index, siblings) = self.branch(height);
+        MmrProof {
+            leaf_index,
+            leaf_count: self.leaf_count(),
+            siblings,
+            peaks: self.peaks(),
+        }
+    }
+}
+
+impl BlockWorkMmr {
+    /// Appends the next block, committed with its height and work.
+    pub fn append(&mut self, block: &OptimizedBlock) {
+        self.append_leaf(WorkNode::leaf(
+            block.compute_block_hash(),
+            block.height,
+            block_work(block.bits),
+        ));
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        work_mmr_root(self.leaf_count(), &self.peaks())
+    }
+
+    /// Inclusion proof of the block at `height` against the current root.
+    pub fn prove(&self, height: u64) -> WorkMmrProof {
+        let (leaf_index, siblings) = self.branch(height);
+        WorkMmrProof {
+            leaf_index,
+            leaf_count: self.leaf_count(),
+            siblings,
+            peaks: self.peaks(),
+        }
+    }
+}
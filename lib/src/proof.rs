@@ -1,46 +1,367 @@
-use bitcoin::hashes::Hash;
-use bitcoin::Block;
-// use crypto_bigint::{Encoding, U256};
+use crypto_bigint::U256;
 
+use crate::mmr::BlockHashesMmr;
 use crate::{AsOptimizedBlock};
-use zk_light_client_core::sha256_merkle::get_merkle_root;
-use zk_light_client_core::{CircuitInput, CircuitPublicValues};
-use zk_light_client_core::btc_light_client::AsLittleEndianBytes;
+use zk_light_client_core::{
+    vkey_digest_to_bytes, Anchor, CircuitInput, CircuitPublicValues, DomainTag, VerificationMode,
+};
+use zk_light_client_core::block_contents::BLOCK_CONTENTS_PROOF_KIND;
+use zk_light_client_core::btc_light_client::{Block as OptimizedBlock, EpochState};
+use zk_light_client_core::commitment::CommitmentScheme;
+use zk_light_client_core::constants::{MAX_BLOCKS, MEDIAN_TIME_SPAN};
+use zk_light_client_core::genesis::Network;
+#[cfg(feature = "checkpoints")]
+use zk_light_client_core::checkpoints::{checkpoints_root, find_checkpoint};
 
+use sp1_sdk::{HashableKey, SP1ProofWithPublicValues, SP1VerifyingKey};
+#[cfg(feature = "checkpoints")]
+use sp1_sdk::{SP1Proof, SP1Stdin};
 // use sp1_sdk::{ExecutionReport, HashableKey, ProverClient, SP1Stdin};
 
 /// The ELF (executable and linkable format) file for the Succinct RISC-V zkVM.
 pub const MAIN_ELF: &[u8] = include_bytes!("../../elf/riscv32im-succinct-zkvm-elf");
 
-pub fn build_block_proof_input(
-    safe_block_height: u64,
-    blocks: &[Block],
-    retarget_block: &Block,
+/// Builds the epoch state of the chain ending at the last block of `safe_block_history`.
+///
+/// `safe_block_history` holds the blocks preceding and including the safe block (only the last
+/// `MEDIAN_TIME_SPAN` are used), `safe_block_chainwork` is the cumulative work up to and including
+/// the safe block.
+pub fn build_epoch_state<T: AsOptimizedBlock>(
+    retarget_block: &T,
     retarget_block_height: u64,
+    safe_block_history: &[T],
+    safe_block_chainwork: U256,
+) -> EpochState {
+    let mut timestamps = [[0u8; 4]; MEDIAN_TIME_SPAN];
+    let history = &safe_block_history[safe_block_history.len().saturating_sub(MEDIAN_TIME_SPAN)..];
+    for (timestamp, block) in timestamps[MEDIAN_TIME_SPAN - history.len()..]
+        .iter_mut()
+        .zip(history)
+    {
+        *timestamp = block.time();
+    }
+
+    EpochState::new(
+        &retarget_block.as_optimized_block(retarget_block_height),
+        safe_block_chainwork,
+        timestamps,
+    )
+}
+
+/// Builds the epoch state of a chain anchored on a compiled-in checkpoint.
+#[cfg(feature = "checkpoints")]
+pub fn build_checkpoint_epoch_state<T: AsOptimizedBlock>(
+    checkpoint_block: &T,
+    checkpoint_height: u64,
+) -> EpochState {
+    let checkpoint_block = checkpoint_block.as_optimized_block(checkpoint_height);
+    find_checkpoint(checkpoint_height, checkpoint_block.compute_block_hash())
+        .expect("Block is not a checkpoint")
+        .epoch_state(&checkpoint_block)
+}
+
+/// Errors of the checks run on the public values of a proof.
+#[derive(Debug)]
+pub enum PublicValuesError {
+    /// The public values are not an encoding of `CircuitPublicValues`
+    Decode(alloy_sol_types::Error),
+    /// The chain is anchored on a proof of another program
+    AnchorVkeyMismatch,
+    /// The proof was made for another network or by another build of the program
+    DomainMismatch,
+    /// The aggregated chunks were proven by another program
+    ChunkVkeyMismatch,
+    /// The public values are those of another kind of proof of the program
+    ProofKindMismatch,
+}
+
+impl std::fmt::Display for PublicValuesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PublicValuesError::Decode(err) => write!(f, "Invalid public values: {err}"),
+            PublicValuesError::AnchorVkeyMismatch => {
+                write!(f, "Proof is anchored on a proof of another program")
+            }
+            PublicValuesError::DomainMismatch => {
+                write!(f, "Proof was made for another network or program build")
+            }
+            PublicValuesError::ChunkVkeyMismatch => {
+                write!(f, "Aggregated chunks were proven by another program")
+            }
+            PublicValuesError::ProofKindMismatch => {
+                write!(f, "Public values are those of another kind of proof")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PublicValuesError {}
+
+impl From<alloy_sol_types::Error> for PublicValuesError {
+    fn from(err: alloy_sol_types::Error) -> Self {
+        PublicValuesError::Decode(err)
+    }
+}
+
+/// Decodes the header chain public values committed by a proof of the main program, block
+/// contents proofs are rejected.
+pub fn decode_public_values(
+    proof: &SP1ProofWithPublicValues,
+) -> Result<CircuitPublicValues, PublicValuesError> {
+    let bytes = proof.public_values.as_slice();
+    if bytes.starts_with(&BLOCK_CONTENTS_PROOF_KIND) {
+        return Err(PublicValuesError::ProofKindMismatch);
+    }
+    Ok(CircuitPublicValues::abi_decode(bytes)?)
+}
+
+/// Decodes the public values of a proof of the main program whose verifying key is `vk`, and runs
+/// the checks the program can't do itself:
+/// - the proof must be of `network` and of this build of the program, as any verifier of the vkey
+///   accepts proofs of every network
+/// - the vkey digest of a proven tip anchor must be the digest of `vk`, see `vkey_digest_to_bytes`
+///   in core
+pub fn check_public_values(
+    proof: &SP1ProofWithPublicValues,
+    vk: &SP1VerifyingKey,
+    network: Network,
+) -> Result<CircuitPublicValues, PublicValuesError> {
+    let public_values = decode_public_values(proof)?;
+    check_chain(&public_values, vk, network)?;
+    Ok(public_values)
+}
+
+// checks of `check_public_values` on the chain proven by the main program whose vkey is `vk`,
+// directly or through the aggregation program
+pub(crate) fn check_chain(
+    public_values: &CircuitPublicValues,
+    vk: &SP1VerifyingKey,
+    network: Network,
+) -> Result<(), PublicValuesError> {
+    check_domain(public_values, network)?;
+    if public_values.anchor_vkey_digest != [0u8; 32]
+        && public_values.anchor_vkey_digest != vkey_digest_to_bytes(&vk.hash_u32())
+    {
+        return Err(PublicValuesError::AnchorVkeyMismatch);
+    }
+    Ok(())
+}
+
+/// Checks the public values were committed for `network` by this build of the program: same
+/// network magic, program version and rule flags.
+pub fn check_domain(
+    public_values: &CircuitPublicValues,
+    network: Network,
+) -> Result<(), PublicValuesError> {
+    if public_values.domain_tag != DomainTag::new(network) {
+        return Err(PublicValuesError::DomainMismatch);
+    }
+    Ok(())
+}
+
+/// Anchors a chain of `network` on the tip of a previous compressed proof of this program. The
+/// proof is written to `stdin` so that the program can verify it recursively.
+#[cfg(feature = "checkpoints")]
+pub fn build_proven_tip_anchor(
+    stdin: &mut SP1Stdin,
+    proof: &SP1ProofWithPublicValues,
+    vk: &SP1VerifyingKey,
+    network: Network,
+) -> Result<Anchor, PublicValuesError> {
+    let SP1Proof::Compressed(reduce_proof) = proof.proof.clone() else {
+        panic!("Anchor proof must be a compressed proof");
+    };
+    let public_values = check_public_values(proof, vk, network)?;
+    stdin.write_proof(reduce_proof, vk.vk.clone());
+
+    Ok(Anchor::ProvenTip {
+        vkey_digest: vk.hash_u32(),
+        public_values,
+    })
+}
+
+// (checkpoints root, anchor vkey digest) committed for the given anchor
+pub(crate) fn anchor_commitments(anchor: &Anchor) -> ([u8; 32], [u8; 32]) {
+    #[cfg(feature = "checkpoints")]
+    {
+        match anchor {
+            Anchor::Checkpoint => (checkpoints_root(), [0u8; 32]),
+            Anchor::ProvenTip { vkey_digest, .. } => {
+                (checkpoints_root(), vkey_digest_to_bytes(vkey_digest))
+            }
+            // chained chunks are anchored by the first chunk of the aggregation
+            Anchor::Chained => ([0u8; 32], [0u8; 32]),
+        }
+    }
+    #[cfg(not(feature = "checkpoints"))]
+    {
+        let _ = anchor;
+        ([0u8; 32], [0u8; 32])
+    }
+}
+
+/// Epoch state at the tip of `input`, the initial epoch state of a chain anchored on its tip.
+pub fn tip_epoch_state(input: &CircuitInput) -> EpochState {
+    advance_epoch_state(
+        input.epoch_state,
+        &input.blocks[0..input.utilized_blocks as usize],
+    )
+}
+
+// the state at the tip is the state at the safe block advanced by every following block
+fn advance_epoch_state(epoch_state: EpochState, blocks: &[OptimizedBlock]) -> EpochState {
+    let mut tip_epoch_state = epoch_state;
+    for block in blocks.iter().skip(1) {
+        tip_epoch_state.advance(block);
+    }
+    tip_epoch_state
+}
+
+/// Builds the input proving `blocks` on top of the safe block `blocks[0]`.
+///
+/// `mmr` holds the block hashes proven so far (up to and including the safe block, or nothing for
+/// the first proof), and is advanced to the tip.
+pub fn build_block_proof_input<T: AsOptimizedBlock>(
+    safe_block_height: u64,
+    blocks: &[T],
+    epoch_state: EpochState,
+    mmr: &mut BlockHashesMmr,
+    anchor: Anchor,
+    network: Network,
+    commitment_scheme: CommitmentScheme,
 ) -> CircuitInput {
-    // convert standard Block into optimized Block (only contains block header info)
+    // convert full blocks, headers or raw headers into optimized Block (only contains block header info)
     let optimized_blocks = &blocks
         .iter()
         .zip(safe_block_height..safe_block_height + blocks.len() as u64)
         .map(|(block, height)| block.as_optimized_block(height))
         .collect::<Vec<_>>();
 
+    let tip_epoch_state = advance_epoch_state(epoch_state, optimized_blocks);
+    let tip_block = optimized_blocks.last().unwrap();
+    let block_hashes = optimized_blocks
+        .iter()
+        .map(|block| block.compute_block_hash())
+        .collect::<Vec<_>>();
+
+    // an empty MMR starts with the safe block
+    let (prev_mmr_peaks, prev_mmr_root) = (mmr.mmr_peaks(), mmr.root());
+    let skip = if mmr.leaf_count() == 0 { 0 } else { 1 };
+    for block_hash in block_hashes.iter().skip(skip) {
+        mmr.append(*block_hash);
+    }
+    #[cfg(feature = "checkpoints")]
+    if let Anchor::Checkpoint = anchor {
+        // fail here rather than in the prover
+        assert!(
+            find_checkpoint(safe_block_height, block_hashes[0]).is_some(),
+            "Safe block {safe_block_height} is not a checkpoint, anchor it on a previous proof \
+             with `build_proven_tip_anchor`"
+        );
+    }
+    let (checkpoints_root, anchor_vkey_digest) = anchor_commitments(&anchor);
+
     CircuitInput::new(
         CircuitPublicValues::new(
-            retarget_block
-                .header
-                .block_hash()
-                .to_byte_array()
-                .to_little_endian(),
+            epoch_state.compute_state_hash(),
+            block_hashes[0],
             safe_block_height,
-            get_merkle_root(
-                blocks
-                    .iter()
-                    .map(|block| block.header.block_hash().to_byte_array().to_little_endian())
-                    .collect::<Vec<_>>(),
-            ),
+            tip_block.compute_block_hash(),
+            tip_block.height,
+            tip_epoch_state.compute_state_hash(),
+            tip_epoch_state.chainwork,
+            commitment_scheme.block_hashes_root(&block_hashes),
+            commitment_scheme,
+            prev_mmr_root,
+            mmr.root(),
+            checkpoints_root,
+            anchor_vkey_digest,
+            VerificationMode::Full,
+            DomainTag::new(network),
         ),
         optimized_blocks.to_vec(),
-        retarget_block.as_optimized_block(retarget_block_height),
+        epoch_state,
+        prev_mmr_peaks,
+        anchor,
+        network,
     )
 }
+
+/// Splits `blocks` on top of the safe block `blocks[0]` into chunks of at most `MAX_BLOCKS` blocks,
+/// the tip of each chunk being the safe block of the next one.
+///
+/// The first chunk uses `anchor`, the following ones are `Anchor::Chained` so that they can be
+/// proven in parallel and merged by the aggregation program. `mmr` is advanced to the tip.
+pub fn build_chunk_inputs<T: AsOptimizedBlock>(
+    safe_block_height: u64,
+    blocks: &[T],
+    epoch_state: EpochState,
+    mmr: &mut BlockHashesMmr,
+    anchor: Anchor,
+    network: Network,
+    commitment_scheme: CommitmentScheme,
+) -> Vec<CircuitInput> {
+    let mut inputs = vec![];
+    let mut epoch_state = epoch_state;
+    let mut anchor = anchor;
+    let mut safe_block_index = 0;
+    loop {
+        let chunk_end = (safe_block_index + MAX_BLOCKS).min(blocks.len());
+        let input = build_block_proof_input(
+            safe_block_height + safe_block_index as u64,
+            &blocks[safe_block_index..chunk_end],
+            epoch_state,
+            mmr,
+            anchor,
+            network,
+            commitment_scheme,
+        );
+        epoch_state = advance_epoch_state(
+            epoch_state,
+            &input.blocks[0..input.utilized_blocks as usize],
+        );
+        inputs.push(input);
+
+        if chunk_end == blocks.len() {
+            break;
+        }
+        // the tip of this chunk is the safe block of the next one
+        safe_block_index = chunk_end - 1;
+        anchor = Anchor::Chained;
+    }
+    inputs
+}
+
+/// Splits a chain starting at the genesis block into batches of at most `MAX_BLOCKS` blocks, each
+/// anchored on the tip of the previous batch, so that the entire history can be proven.
+///
+/// Only the first batch is anchored on genesis. Every other batch is `Anchor::Chained` and commits
+/// no anchor, so its proof alone says nothing about the chain below its safe block: the batches
+/// must be chained outside of this function, either by merging the batch proofs with the
+/// aggregation program, or with the `checkpoints` feature by replacing the anchor of each batch
+/// with `build_proven_tip_anchor` of the previous batch proof. The returned MMR holds every block
+/// hash.
+pub fn build_genesis_proof_inputs<T: AsOptimizedBlock>(
+    network: Network,
+    commitment_scheme: CommitmentScheme,
+    blocks: &[T],
+) -> (Vec<CircuitInput>, BlockHashesMmr) {
+    let genesis_block = network.genesis_block();
+    assert_eq!(
+        blocks[0].as_optimized_block(0).compute_block_hash(),
+        genesis_block.compute_block_hash(),
+        "First block is not the genesis block"
+    );
+
+    let mut mmr = BlockHashesMmr::new(0);
+    let inputs = build_chunk_inputs(
+        0,
+        blocks,
+        network.genesis_epoch_state(),
+        &mut mmr,
+        Anchor::Checkpoint,
+        network,
+        commitment_scheme,
+    );
+    (inputs, mmr)
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use bitcoin::block::Header;
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::hashes::Hash;
+use bitcoin::merkle_tree::MerkleBlockError;
+use bitcoin::MerkleBlock;
+use serde::Deserialize;
+
+use zk_light_client_core::block_contents::TransactionProof;
+use zk_light_client_core::btc_light_client::{AsLittleEndianBytes, Block as OptimizedBlock};
+use zk_light_client_core::sha256_merkle::{get_merkle_root_from_branch, hash_pairs};
+
+/// Errors of the parsing of an SPV proof.
+#[derive(Debug)]
+pub enum SpvError {
+    /// The proof is not a serialized `CMerkleBlock`
+    Decode(bitcoin::consensus::encode::Error),
+    /// The partial merkle tree is malformed or does not match the header merkle root
+    PartialMerkleTree(MerkleBlockError),
+    /// The Electrum response is not a `blockchain.transaction.get_merkle` result
+    Json(serde_json::Error),
+    /// A hash of the Electrum branch is not 32 bytes of hex
+    InvalidHash(String),
+}
+
+impl std::fmt::Display for SpvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SpvError::Decode(err) => write!(f, "Failed to parse merkle block: {err}"),
+            SpvError::PartialMerkleTree(err) => write!(f, "Invalid partial merkle tree: {err}"),
+            SpvError::Json(err) => write!(f, "Failed to parse Electrum merkle proof: {err}"),
+            SpvError::InvalidHash(hash) => write!(f, "Invalid merkle hash: {hash}"),
+        }
+    }
+}
+
+impl std::error::Error for SpvError {}
+
+impl From<bitcoin::consensus::encode::Error> for SpvError {
+    fn from(err: bitcoin::consensus::encode::Error) -> Self {
+        SpvError::Decode(err)
+    }
+}
+
+impl From<MerkleBlockError> for SpvError {
+    fn from(err: MerkleBlockError) -> Self {
+        SpvError::PartialMerkleTree(err)
+    }
+}
+
+impl From<serde_json::Error> for SpvError {
+    fn from(err: serde_json::Error) -> Self {
+        SpvError::Json(err)
+    }
+}
+
+/// Inclusion of a txid in the transaction merkle tree of a block, imported from an SPV proof.
+/// Hashes are in RPC byte order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxMerkleBranch {
+    pub txid: [u8; 32],
+    pub index: u32,
+    /// Siblings from the bottom up
+    pub branch: Vec<[u8; 32]>,
+}
+
+impl TxMerkleBranch {
+    /// Transaction merkle root in header byte order.
+    pub fn merkle_root(&self) -> [u8; 32] {
+        get_merkle_root_from_branch(self.txid, self.index, &self.branch).to_little_endian()
+    }
+
+    /// Builds the circuit proof of `transaction`, checking the branch against the header of the
+    /// proven chain.
+    pub fn transaction_proof(
+        &self,
+        transaction: &bitcoin::Transaction,
+        block: &OptimizedBlock,
+    ) -> TransactionProof {
+        assert_eq!(
+            transaction
+                .compute_txid()
+                .to_byte_array()
+                .to_little_endian(),
+            self.txid,
+            "Transaction does not match the SPV proof"
+        );
+        assert_eq!(
+            self.merkle_root(),
+            block.merkle_root,
+            "SPV proof does not match the block merkle root"
+        );
+
+        TransactionProof {
+            height: block.height,
+            index: self.index,
+            transaction: serialize(transaction),
+            branch: self.branch.clone(),
+        }
+    }
+}
+
+// depth-first walk of a BIP37 partial merkle tree, as done by `CPartialMerkleTree`
+struct PartialTreeWalker<'a> {
+    bits: &'a [bool],
+    hashes: Vec<[u8; 32]>,
+    transaction_count: usize,
+    bits_used: usize,
+    hashes_used: usize,
+    // (height, position) -> hash, in RPC byte order
+    nodes: HashMap<(usize, usize), [u8; 32]>,
+}
+
+impl PartialTreeWalker<'_> {
+    fn level_width(&self, height: usize) -> usize {
+        (self.transaction_count + (1 << height) - 1) >> height
+    }
+
+    fn walk(&mut self, height: usize, position: usize) -> [u8; 32] {
+        let parent_of_match = self.bits[self.bits_used];
+        self.bits_used += 1;
+        let hash = if height == 0 || !parent_of_match {
+            self.hashes_used += 1;
+            self.hashes[self.hashes_used - 1]
+        } else {
+            let left = self.walk(height - 1, position * 2);
+            // the last node of an odd level is paired with itself
+            let right = if position * 2 + 1 < self.level_width(height - 1) {
+                self.walk(height - 1, position * 2 + 1)
+            } else {
+                left
+            };
+            hash_pairs(left, right)
+        };
+        self.nodes.insert((height, position), hash);
+        hash
+    }
+}
+
+/// Parses the output of `bitcoin-cli gettxoutproof`, a serialized `CMerkleBlock` (BIP37), into the
+/// header and the branch of every matched transaction. The partial merkle tree is checked against
+/// the header merkle root.
+pub fn parse_txout_proof(bytes: &[u8]) -> Result<(Header, Vec<TxMerkleBranch>), SpvError> {
+    let merkle_block: MerkleBlock = deserialize(bytes)?;
+    let (mut matches, mut indexes) = (vec![], vec![]);
+    merkle_block.extract_matches(&mut matches, &mut indexes)?;
+
+    // [1] walk the tree again to keep the hash of every node, which is either given or computed
+    let mut walker = PartialTreeWalker {
+        bits: merkle_block.txn.bits(),
+        hashes: merkle_block
+            .txn
+            .hashes()
+            .iter()
+            .map(|hash| hash.to_byte_array().to_little_endian())
+            .collect(),
+        transaction_count: merkle_block.txn.num_transactions() as usize,
+        bits_used: 0,
+        hashes_used: 0,
+        nodes: HashMap::new(),
+    };
+    let mut tree_height = 0;
+    while walker.level_width(tree_height) > 1 {
+        tree_height += 1;
+    }
+    walker.walk(tree_height, 0);
+
+    // [2] the branch of a match is the sibling at every level, or the node itself at the odd end
+    let branches = matches
+        .iter()
+        .zip(indexes)
+        .map(|(txid, index)| TxMerkleBranch {
+            txid: txid.to_byte_array().to_little_endian(),
+            index,
+            branch: (0..tree_height)
+                .map(|height| {
+                    let position = index as usize >> height;
+                    let sibling = (position ^ 1).min(walker.level_width(height) - 1);
+                    walker.nodes[&(height, sibling)]
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    for branch in branches.iter() {
+        assert_eq!(
+            branch.merkle_root(),
+            merkle_block.header.merkle_root.to_byte_array(),
+            "SPV proof does not match the block merkle root"
+        );
+    }
+    Ok((merkle_block.header, branches))
+}
+
+// response of the Electrum `blockchain.transaction.get_merkle` method
+#[derive(Deserialize)]
+struct ElectrumMerkle {
+    block_height: u64,
+    merkle: Vec<String>,
+    pos: u32,
+}
+
+/// Parses the JSON response of the Electrum `blockchain.transaction.get_merkle` method for `txid`
+/// (in RPC byte order) into the height of its block and its branch. The response has no header,
+/// the branch is checked by `TxMerkleBranch::transaction_proof`.
+pub fn parse_electrum_merkle(
+    json: &str,
+    txid: [u8; 32],
+) -> Result<(u64, TxMerkleBranch), SpvError> {
+    let response: ElectrumMerkle = serde_json::from_str(json)?;
+    let branch = response
+        .merkle
+        .iter()
+        .map(|hash| {
+            <[u8; 32] as bitcoin::hashes::hex::FromHex>::from_hex(hash)
+                .map_err(|_| SpvError::InvalidHash(hash.clone()))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((
+        response.block_height,
+        TxMerkleBranch {
+            txid,
+            index: response.pos,
+            branch,
+        },
+    ))
+}
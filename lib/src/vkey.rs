@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use crate::aggregation::AGGREGATION_ELF;
+use crate::keys;
+use crate::proof::MAIN_ELF;
+use crate::to_hex_string;
+use zk_light_client_core::vkey_digest_to_bytes;
+
+use bitcoin::hashes::hex::FromHex;
+use sp1_sdk::{HashableKey, ProverClient, SP1VerifyingKey};
+
+/// Programs whose vkey is pinned, by name.
+pub const PROGRAMS: [(&str, &[u8]); 2] = [("main", MAIN_ELF), ("aggregation", AGGREGATION_ELF)];
+
+/// Directory of the checked-in vkey hashes the deployed verifier contracts are pinned to, one
+/// `<name>.vkey` file per program: `#` comment lines, then the hash, which is missing until the
+/// program is pinned.
+pub fn pinned_vkeys_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../vkeys")
+}
+
+/// The vkey hash (bytes32) the verifier contracts check proofs against.
+pub fn vkey_hash(vk: &SP1VerifyingKey) -> [u8; 32] {
+    let bytes = vk.bytes32();
+    <[u8; 32]>::from_hex(bytes.trim_start_matches("0x")).expect("Invalid vkey hash")
+}
+
+/// The vkey digest committed by the programs verifying proofs of `vk` recursively, i.e. the
+/// aggregated chunks and the proven tip anchors.
+pub fn vkey_digest(vk: &SP1VerifyingKey) -> [u8; 32] {
+    vkey_digest_to_bytes(&vk.hash_u32())
+}
+
+/// The vkey hash of the program built from `elf`.
+pub fn program_vkey_hash(client: &ProverClient, elf: &[u8]) -> [u8; 32] {
+    let (_pk, vk) = keys::setup(client, elf);
+    vkey_hash(&vk)
+}
+
+/// Parses the contents of a `<name>.vkey` file, `None` if it holds no hash yet.
+pub fn parse_pinned_vkey_hash(contents: &str) -> Option<[u8; 32]> {
+    let hex_string = contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+    Some(
+        <[u8; 32]>::from_hex(hex_string.trim_start_matches("0x"))
+            .expect("Invalid pinned vkey hash"),
+    )
+}
+
+/// The contents of the `<name>.vkey` file pinning `vkey_hash`.
+pub fn pinned_vkey_file(name: &str, vkey_hash: [u8; 32]) -> String {
+    format!(
+        "# vkey hash of the {name} program, pinned with `vkey --pin`\n0x{}\n",
+        to_hex_string(&vkey_hash)
+    )
+}
+
+/// The checked-in vkey hash of the program `name`, if it was pinned.
+pub fn pinned_vkey_hash(name: &str) -> Option<[u8; 32]> {
+    let path = pinned_vkeys_dir().join(format!("{name}.vkey"));
+    parse_pinned_vkey_hash(&std::fs::read_to_string(path).ok()?)
+}
+
+/// Pins the vkey hash of the program `name`, to be checked in with the deployed contracts.
+pub fn pin_vkey_hash(name: &str, vkey_hash: [u8; 32]) {
+    let dir = pinned_vkeys_dir();
+    std::fs::create_dir_all(&dir).expect("Failed to create vkeys directory");
+    std::fs::write(
+        dir.join(format!("{name}.vkey")),
+        pinned_vkey_file(name, vkey_hash),
+    )
+    .expect("Failed to write pinned vkey hash");
+}
+
+/// Panics if the program `name` was rebuilt into a vkey other than the pinned one, or was never
+/// pinned, so that its proofs aren't submitted to verifier contracts that would reject them.
+pub fn assert_pinned_vkey(name: &str, vk: &SP1VerifyingKey) {
+    let pinned_vkey_hash = pinned_vkey_hash(name).unwrap_or_else(|| {
+        panic!("No pinned vkey hash for the {name} program, pin it with `vkey --pin`")
+    });
+    assert_eq!(
+        to_hex_string(&vkey_hash(vk)),
+        to_hex_string(&pinned_vkey_hash),
+        "The {name} program vkey doesn't match the pinned vkey, the ELF changed"
+    );
+}
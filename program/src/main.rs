@@ -1,25 +1,39 @@
 #![no_main]
 sp1_zkvm::entrypoint!(main);
 
-use alloy_sol_types::private::{FixedBytes};
-use alloy_sol_types::SolType;
-use zk_light_client_core::{validate_block, CircuitInput, ProofPublicInputs};
+use zk_light_client_core::{cycle_tracker_end, cycle_tracker_start, validate, ProgramInput};
+#[cfg(feature = "checkpoints")]
+use zk_light_client_core::{sha256_merkle::sha256_hash, Anchor};
 
 pub fn main() {
     // Read an input to the program.
     // Behind the scenes, this compiles down to a custom system call which handles reading inputs
     // from the prover.
-    let circuit_input = sp1_zkvm::io::read::<CircuitInput>();
+    cycle_tracker_start!("read input");
+    let program_input = sp1_zkvm::io::read::<ProgramInput>();
+    cycle_tracker_end!("read input");
+    #[cfg(feature = "checkpoints")]
+    let anchor = program_input.anchor();
 
-    // circuit logic
-    let circuit_public_input = validate_block(circuit_input);
+    // circuit logic, returns the ABI-encoded public values
+    cycle_tracker_start!("validate");
+    let bytes = validate(program_input);
+    cycle_tracker_end!("validate");
 
-    // Encode the public values of the program.
-    let bytes = ProofPublicInputs::abi_encode(&ProofPublicInputs {
-        retarget_block_hash: FixedBytes::from(circuit_public_input.retarget_block_hash),
-        safe_block_height: circuit_public_input.safe_block_height,
-        block_hashes_merkle_root: FixedBytes::from(circuit_public_input.block_hashes_merkle_root),
-    });
+    // recursively verify the proof whose tip this chain is anchored on
+    #[cfg(feature = "checkpoints")]
+    if let Anchor::ProvenTip {
+        vkey_digest,
+        public_values,
+    } = anchor
+    {
+        cycle_tracker_start!("anchor proof verification");
+        sp1_zkvm::lib::verify::verify_sp1_proof(
+            &vkey_digest,
+            &sha256_hash(&public_values.abi_encode()),
+        );
+        cycle_tracker_end!("anchor proof verification");
+    }
 
     // Commit to the public values of the program. The final proof will have a commitment to all the
     // bytes that were committed to.
@@ -1,5 +1,30 @@
-use sp1_helper::build_program_with_args;
+use sp1_helper::{build_program_with_args, BuildArgs};
 
 fn main() {
-    build_program_with_args("../program", Default::default())
+    // the program is built with the rules of the host, so that both commit the same anchors
+    let mut features = vec![];
+    if std::env::var("CARGO_FEATURE_CHECKPOINTS").is_ok() {
+        features.push("checkpoints".to_string());
+    }
+    // cycle tracker sections, for both programs
+    let mut aggregation_features = vec![];
+    if std::env::var("CARGO_FEATURE_PROFILING").is_ok() {
+        features.push("profiling".to_string());
+        aggregation_features.push("profiling".to_string());
+    }
+    build_program_with_args(
+        "../program",
+        BuildArgs {
+            features,
+            ..Default::default()
+        },
+    );
+    build_program_with_args(
+        "../aggregation",
+        BuildArgs {
+            elf_name: "aggregation-elf".to_string(),
+            features: aggregation_features,
+            ..Default::default()
+        },
+    )
 }
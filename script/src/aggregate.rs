@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+
+use zk_light_client_core::genesis::Network;
+use zk_light_client_core::{Anchor, ProgramInput};
+use zk_light_client_lib::aggregation::{
+    aggregated_chunk, check_aggregation_public_values, chunk_block_hashes, prove_aggregation,
+    AGGREGATION_ELF,
+};
+use zk_light_client_lib::artifact::{save_proof, ProofMetadata, ProofSystem};
+use zk_light_client_lib::headers::get_chunk_inputs;
+use zk_light_client_lib::{keys, proof, to_hex_string, vkey};
+
+use clap::Parser;
+use sp1_sdk::{ProverClient, SP1ProofWithPublicValues, SP1Stdin};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Directory of the `block_<height>.hex` files
+    #[arg(long, default_value = "../tests/data")]
+    data_dir: String,
+
+    /// Height of the first block, the safe block is `SAFE_BLOCK_INDEX` blocks later. With the
+    /// `checkpoints` feature the range starts on the latest checkpoint at or below the safe block
+    #[arg(long, default_value_t = 854373)]
+    first_block_height: u64,
+
+    /// Number of blocks proven on top of the safe block, split into chunks of `MAX_BLOCKS`
+    #[arg(long, default_value_t = 1500)]
+    blocks: usize,
+
+    /// Number of chunks proven at the same time
+    #[arg(long, default_value_t = 2)]
+    parallelism: usize,
+
+    /// Directory of the saved chunk and aggregation proofs
+    #[arg(long, default_value = "proofs")]
+    output_dir: PathBuf,
+}
+
+fn main() {
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
+
+    // Parse command line arguments
+    let args = Args::parse();
+
+    let chunk_inputs = get_chunk_inputs(
+        &args.data_dir,
+        args.first_block_height,
+        args.blocks,
+        Anchor::Checkpoint,
+    );
+    println!(
+        "{} chunk inputs generated successfully.",
+        chunk_inputs.len()
+    );
+
+    // Setup the prover client.
+    let client = ProverClient::new();
+    let (pk, vk) = keys::setup(&client, proof::MAIN_ELF);
+    vkey::assert_pinned_vkey("main", &vk);
+
+    // Prove the chunks in parallel, the aggregation program verifies compressed proofs
+    let mut chunk_proofs: Vec<SP1ProofWithPublicValues> = vec![];
+    for batch in chunk_inputs.chunks(args.parallelism.max(1)) {
+        let batch_proofs = std::thread::scope(|scope| {
+            let handles = batch
+                .iter()
+                .map(|chunk_input| {
+                    let (client, pk) = (&client, &pk);
+                    scope.spawn(move || {
+                        let mut stdin = SP1Stdin::new();
+                        stdin.write(&ProgramInput::Full(*chunk_input));
+                        client
+                            .prove(pk, stdin)
+                            .compressed()
+                            .run()
+                            .expect("failed to generate chunk proof")
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("chunk prover panicked"))
+                .collect::<Vec<_>>()
+        });
+        chunk_proofs.extend(batch_proofs);
+        println!("{} chunk proofs generated.", chunk_proofs.len());
+    }
+
+    // the chunk proofs can be wrapped again later by `wrap`
+    for (chunk_input, chunk_proof) in chunk_inputs.iter().zip(&chunk_proofs) {
+        let public_values = &chunk_input.public_values;
+        let name = format!(
+            "{}-{}",
+            public_values.safe_block_height, public_values.tip_block_height
+        );
+        let proof_path = save_proof(
+            &args.output_dir,
+            &name,
+            chunk_proof,
+            &ProofMetadata::new(
+                public_values,
+                &chunk_block_hashes(chunk_input),
+                chunk_proof,
+                &vk,
+            ),
+        );
+        println!("Chunk proof saved to {}", proof_path.display());
+    }
+
+    // Aggregate the chunk proofs into a single proof of the entire range
+    let (aggregation_pk, aggregation_vk) = keys::setup(&client, AGGREGATION_ELF);
+    vkey::assert_pinned_vkey("aggregation", &aggregation_vk);
+    let proof = prove_aggregation(
+        &client,
+        &aggregation_pk,
+        chunk_inputs.iter().map(aggregated_chunk).collect(),
+        &chunk_proofs,
+        &vk,
+        ProofSystem::Groth16,
+    );
+
+    println!("Successfully generated aggregation proof!");
+
+    // Verify the proof.
+    client
+        .verify(&proof, &aggregation_vk)
+        .expect("failed to verify proof");
+    println!("Successfully verified proof!");
+
+    let (public_values, chunk_count) =
+        check_aggregation_public_values(&proof, &vk, Network::Bitcoin)
+            .expect("invalid public values");
+    println!(
+        "Aggregated {} chunks, tip height {}",
+        chunk_count, public_values.tip_block_height
+    );
+    let name = format!(
+        "{}-{}",
+        public_values.safe_block_height, public_values.tip_block_height
+    );
+    let proof_path = save_proof(
+        &args.output_dir,
+        &name,
+        &proof,
+        &ProofMetadata::new(&public_values, &[], &proof, &aggregation_vk),
+    );
+    println!("Proof saved to {}", proof_path.display());
+    println!(
+        "Public Inputs: {:?}",
+        to_hex_string(proof.public_values.to_vec().as_slice())
+    );
+    println!("Solidity Ready Proof: {:?}", to_hex_string(&proof.bytes()));
+}
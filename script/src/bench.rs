@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use zk_light_client_core::{Anchor, ProgramInput};
+use zk_light_client_lib::headers::{get_circuit_input, get_regtest_circuit_input};
+use zk_light_client_lib::proof;
+
+use clap::Parser;
+use serde_json::{json, Value};
+use sp1_sdk::{ProverClient, SP1Stdin};
+
+// the safe block is `SAFE_BLOCK_INDEX` blocks later
+const FIRST_BLOCK_HEIGHT: u64 = 854373;
+
+/// Reports the cycles of the main program over runs of increasing length. The cycle tracker
+/// sections are only printed by a program built with the `profiling` feature.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Directory of the mainnet `block_<height>.hex` files from height 854373, regtest blocks are
+    /// mined instead when not given
+    #[arg(long)]
+    data_dir: Option<String>,
+
+    /// Number of blocks of each run, the safe block included
+    #[arg(long, value_delimiter = ',', default_value = "1,10,100,500")]
+    blocks: Vec<usize>,
+
+    /// JSON report of the cycle counts of each run
+    #[arg(long, default_value = "bench.json")]
+    output: String,
+
+    /// Previous JSON report, the benchmark fails if a run of the same length takes more cycles
+    /// than in it by over `threshold` percent
+    #[arg(long)]
+    baseline: Option<String>,
+
+    /// Cycle increase over the baseline allowed, in percent
+    #[arg(long, default_value = "1.0")]
+    threshold: f64,
+}
+
+fn main() {
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
+
+    // Parse command line arguments
+    let args = Args::parse();
+
+    let client = ProverClient::new();
+    let data = args.data_dir.as_deref().unwrap_or("regtest");
+
+    let mut runs = vec![];
+    for num_blocks in args.blocks {
+        let circuit_input = match &args.data_dir {
+            Some(data_dir) => {
+                get_circuit_input(data_dir, FIRST_BLOCK_HEIGHT, num_blocks, Anchor::Checkpoint)
+            }
+            None => get_regtest_circuit_input(num_blocks),
+        };
+        let mut stdin = SP1Stdin::new();
+        stdin.write(&ProgramInput::Full(circuit_input));
+
+        // cycles of the sections annotated with the cycle tracker, summed over the run
+        let (_output, report) = client.execute(proof::MAIN_ELF, stdin).run().unwrap();
+        println!(
+            "{num_blocks} blocks: {} cycles",
+            report.total_instruction_count()
+        );
+        runs.push(json!({
+            "blocks": num_blocks,
+            "total_cycles": report.total_instruction_count(),
+            "sections": report.cycle_tracker,
+        }));
+    }
+
+    std::fs::write(
+        &args.output,
+        serde_json::to_string_pretty(&json!({ "data": data, "runs": runs })).unwrap(),
+    )
+    .expect("Failed to write benchmark report");
+    println!("Benchmark report written to {}", args.output);
+
+    if let Some(baseline) = args.baseline {
+        let baseline: Value = serde_json::from_str(
+            &std::fs::read_to_string(&baseline).expect("Failed to read baseline report"),
+        )
+        .expect("Failed to parse baseline report");
+        assert_eq!(
+            baseline["data"], data,
+            "Baseline report was run on other blocks"
+        );
+        let baseline_cycles = baseline["runs"]
+            .as_array()
+            .expect("Baseline report has no runs")
+            .iter()
+            .map(|run| (run["blocks"].as_u64(), run["total_cycles"].as_u64()))
+            .collect::<HashMap<_, _>>();
+
+        let mut regression = false;
+        for run in runs.iter() {
+            let Some(Some(baseline_cycles)) = baseline_cycles.get(&run["blocks"].as_u64()) else {
+                println!("{} blocks: not in the baseline", run["blocks"]);
+                continue;
+            };
+            let cycles = run["total_cycles"].as_u64().unwrap();
+            let increase = (cycles as f64 / *baseline_cycles as f64 - 1.0) * 100.0;
+            println!(
+                "{} blocks: {increase:+.2}% cycles over the baseline",
+                run["blocks"]
+            );
+            regression |= increase > args.threshold;
+        }
+        if regression {
+            eprintln!(
+                "Cycles increased by over {}% over the baseline",
+                args.threshold
+            );
+            std::process::exit(1);
+        }
+    }
+}
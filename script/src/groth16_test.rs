@@ -1,39 +1,21 @@
-use bitcoin::consensus::encode::deserialize;
-
-use bitcoin::Block;
-
-use zk_light_client_core::CircuitInput;
-use zk_light_client_lib::proof::{self, build_block_proof_input};
-use zk_light_client_lib::{get_retarget_height_from_block_height, load_hex_bytes, to_hex_string};
+use zk_light_client_core::genesis::Network;
+use zk_light_client_core::{Anchor, ProgramInput};
+use zk_light_client_lib::headers::get_circuit_input;
+use zk_light_client_lib::{keys, proof, to_hex_string, vkey};
 
 use clap::Parser;
 use sp1_sdk::{ProverClient, SP1Stdin};
 
-fn get_test_case_circuit_input() -> CircuitInput {
-    let num_blocks = 500;
-    let mined_blocks = (854373..(854373 + num_blocks)).map(|height| deserialize::<Block>(&load_hex_bytes(format!("/root/blocks/data/block_{height}.hex").as_str())).unwrap()).collect::<Vec<_>>();
-
-    let mined_block_height = 854374;
-    let retarget_block_height = get_retarget_height_from_block_height(mined_block_height);
-    let mined_retarget_block = deserialize::<Block>(&load_hex_bytes(
-        format!("/root/blocks/data/block_{retarget_block_height}.hex").as_str(),
-    ))
-    .unwrap();
-
-    build_block_proof_input(
-        mined_blocks.first().unwrap().bip34_block_height().unwrap(),
-        mined_blocks.as_slice(),
-        &mined_retarget_block,
-        retarget_block_height,
-    )
-}
-
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Run only the execute block without proof generation
     #[arg(long)]
     execute: bool,
+
+    /// Directory of the `block_<height>.hex` files
+    #[arg(long, default_value = "../tests/data")]
+    data_dir: String,
 }
 
 fn main() {
@@ -43,7 +25,8 @@ fn main() {
     // Parse command line arguments
     let args = Args::parse();
 
-    let circuit_input = get_test_case_circuit_input();
+    // the 10 blocks before the safe block only provide its timestamp history
+    let circuit_input = get_circuit_input(&args.data_dir, 854373, 490, Anchor::Checkpoint);
 
     println!("Circuit input generated successfully.");
 
@@ -53,7 +36,7 @@ fn main() {
     // Setup the inputs.
     let mut stdin = SP1Stdin::new();
 
-    stdin.write(&circuit_input);
+    stdin.write(&ProgramInput::Full(circuit_input));
     println!("Inputs serialized successfully.");
 
     if args.execute {
@@ -63,7 +46,8 @@ fn main() {
         println!("Number of cycles: {}", report.total_instruction_count());
     } else {
         // Setup the program for proving.
-        let (pk, vk) = client.setup(proof::MAIN_ELF);
+        let (pk, vk) = keys::setup(&client, proof::MAIN_ELF);
+        vkey::assert_pinned_vkey("main", &vk);
 
         // Generate the proof
         let proof = client
@@ -76,6 +60,8 @@ fn main() {
 
         // Verify the proof.
         client.verify(&proof, &vk).expect("failed to verify proof");
+        proof::check_public_values(&proof, &vk, Network::Bitcoin)
+            .expect("invalid public values");
         println!("Successfully verified proof!");
         println!(
             "Public Inputs: {:?}",
@@ -0,0 +1,168 @@
+#[cfg(feature = "checkpoints")]
+use std::path::Path;
+use std::path::PathBuf;
+
+use zk_light_client_core::commitment::CommitmentScheme;
+use zk_light_client_core::genesis::Network;
+use zk_light_client_core::{Anchor, CircuitInput, ProgramInput};
+use zk_light_client_lib::aggregation::chunk_block_hashes;
+#[cfg(feature = "checkpoints")]
+use zk_light_client_lib::artifact::{load_proof, load_tip_state};
+use zk_light_client_lib::artifact::{save_proof, save_tip_state, ProofMetadata, TipState};
+#[cfg(feature = "checkpoints")]
+use zk_light_client_lib::headers::get_anchored_circuit_input;
+use zk_light_client_lib::headers::load_anchor_chain;
+use zk_light_client_lib::mmr::BlockHashesMmr;
+use zk_light_client_lib::{keys, proof, vkey};
+
+use clap::Parser;
+use sp1_sdk::{ProverClient, SP1Stdin, SP1VerifyingKey};
+
+/// Generates a compressed proof of a range and saves it, to be wrapped later by `wrap`, alone or
+/// with the proofs of the following chunks.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Directory of the `block_<height>.hex` files
+    #[arg(long, default_value = "../tests/data")]
+    data_dir: String,
+
+    /// Height of the first block, the safe block is `SAFE_BLOCK_INDEX` blocks later. With the
+    /// `checkpoints` feature the range starts on the latest checkpoint at or below the safe block
+    #[arg(long, default_value_t = 854373)]
+    first_block_height: u64,
+
+    /// Number of blocks, the safe block included
+    #[arg(long, default_value_t = 500)]
+    blocks: usize,
+
+    /// Compressed proof saved by `prove` to anchor this range on, its tip becomes the safe block
+    /// and `--first-block-height` is ignored
+    #[cfg(feature = "checkpoints")]
+    #[arg(long)]
+    anchor_proof: Option<PathBuf>,
+
+    /// Directory of the saved proofs
+    #[arg(long, default_value = "proofs")]
+    output_dir: PathBuf,
+}
+
+fn main() {
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
+
+    // Parse command line arguments
+    let args = Args::parse();
+
+    // Setup the prover client.
+    let client = ProverClient::new();
+    let (pk, vk) = keys::setup(&client, proof::MAIN_ELF);
+    vkey::assert_pinned_vkey("main", &vk);
+
+    let mut stdin = SP1Stdin::new();
+    let (circuit_input, mmr) = get_input(&args, &mut stdin, &vk);
+    println!("Circuit input generated successfully.");
+    stdin.write(&ProgramInput::Full(circuit_input));
+
+    // Only compress, wrapping to Groth16 or PLONK is left to `wrap`
+    let proof = client
+        .prove(&pk, stdin)
+        .compressed()
+        .run()
+        .expect("failed to generate proof");
+    client.verify(&proof, &vk).expect("failed to verify proof");
+    proof::check_public_values(&proof, &vk, Network::Bitcoin).expect("invalid public values");
+    println!("Successfully generated compressed proof!");
+
+    let public_values = &circuit_input.public_values;
+    let name = format!(
+        "{}-{}",
+        public_values.safe_block_height, public_values.tip_block_height
+    );
+    let proof_path = save_proof(
+        &args.output_dir,
+        &name,
+        &proof,
+        &ProofMetadata::new(
+            public_values,
+            &chunk_block_hashes(&circuit_input),
+            &proof,
+            &vk,
+        ),
+    );
+    println!("Proof saved to {}", proof_path.display());
+
+    // the next range is anchored on this proof from its tip state
+    let tip_state = TipState {
+        epoch_state: proof::tip_epoch_state(&circuit_input),
+        mmr,
+    };
+    let tip_state_path = save_tip_state(&args.output_dir, &name, &tip_state);
+    println!("Tip state saved to {}", tip_state_path.display());
+}
+
+// Input of the range and the MMR at its tip, anchored on `--anchor-proof` if given
+#[cfg(feature = "checkpoints")]
+fn get_input(
+    args: &Args,
+    stdin: &mut SP1Stdin,
+    vk: &SP1VerifyingKey,
+) -> (CircuitInput, BlockHashesMmr) {
+    match &args.anchor_proof {
+        Some(anchor_proof_path) => get_anchored_input(args, anchor_proof_path, stdin, vk),
+        None => get_checkpoint_input(args),
+    }
+}
+
+#[cfg(not(feature = "checkpoints"))]
+fn get_input(
+    args: &Args,
+    _stdin: &mut SP1Stdin,
+    _vk: &SP1VerifyingKey,
+) -> (CircuitInput, BlockHashesMmr) {
+    get_checkpoint_input(args)
+}
+
+// Input anchored on a checkpoint, with the MMR at its tip
+fn get_checkpoint_input(args: &Args) -> (CircuitInput, BlockHashesMmr) {
+    let (safe_block_height, headers, epoch_state) =
+        load_anchor_chain(&args.data_dir, args.first_block_height, args.blocks);
+    let mut mmr = BlockHashesMmr::new(safe_block_height);
+    let circuit_input = proof::build_block_proof_input(
+        safe_block_height,
+        &headers,
+        epoch_state,
+        &mut mmr,
+        Anchor::Checkpoint,
+        Network::Bitcoin,
+        CommitmentScheme::Sha256,
+    );
+    (circuit_input, mmr)
+}
+
+// Input anchored on the tip of a proof saved by `prove`, with the MMR at its tip
+#[cfg(feature = "checkpoints")]
+fn get_anchored_input(
+    args: &Args,
+    anchor_proof_path: &Path,
+    stdin: &mut SP1Stdin,
+    vk: &SP1VerifyingKey,
+) -> (CircuitInput, BlockHashesMmr) {
+    let (anchor_proof, metadata, anchor_name) = load_proof(anchor_proof_path);
+    let anchor = proof::build_proven_tip_anchor(stdin, &anchor_proof, vk, Network::Bitcoin)
+        .expect("invalid anchor proof");
+    let mut tip_state = load_tip_state(
+        anchor_proof_path
+            .parent()
+            .expect("Invalid anchor proof path"),
+        &anchor_name,
+    );
+    let circuit_input = get_anchored_circuit_input(
+        &args.data_dir,
+        metadata.tip_block_height,
+        args.blocks,
+        &mut tip_state,
+        anchor,
+    );
+    (circuit_input, tip_state.mmr)
+}
@@ -0,0 +1,56 @@
+use zk_light_client_lib::vkey::{
+    pin_vkey_hash, pinned_vkey_hash, vkey_digest, vkey_hash, PROGRAMS,
+};
+use zk_light_client_lib::{keys, to_hex_string};
+
+use clap::Parser;
+use sp1_sdk::ProverClient;
+
+/// Prints the vkey hash and digest of every program, and fails if the hash is not pinned or doesn't
+/// match the pinned one.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Pin the current vkey hashes, once the matching verifier contracts are deployed
+    #[arg(long)]
+    pin: bool,
+}
+
+fn main() {
+    // Parse command line arguments
+    let args = Args::parse();
+
+    let client = ProverClient::new();
+
+    let mut mismatch = false;
+    for (name, elf) in PROGRAMS {
+        let (_pk, vk) = keys::setup(&client, elf);
+        let vkey_hash = vkey_hash(&vk);
+        let pinned = pinned_vkey_hash(name);
+        println!("{name}: 0x{}", to_hex_string(&vkey_hash));
+        // committed by the programs verifying its proofs, e.g. `programVKeyDigest` of the contract
+        println!("{name} digest: 0x{}", to_hex_string(&vkey_digest(&vk)));
+
+        if args.pin {
+            pin_vkey_hash(name, vkey_hash);
+            println!("{name}: pinned");
+        } else {
+            match pinned {
+                Some(pinned) if pinned == vkey_hash => println!("{name}: matches the pinned vkey"),
+                Some(pinned) => {
+                    println!("{name}: pinned vkey is 0x{}", to_hex_string(&pinned));
+                    mismatch = true;
+                }
+                None => {
+                    println!("{name}: not pinned");
+                    mismatch = true;
+                }
+            }
+        }
+    }
+
+    if mismatch {
+        eprintln!("Program vkeys don't match the pinned vkeys");
+        std::process::exit(1);
+    }
+}
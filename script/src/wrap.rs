@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+
+use zk_light_client_core::genesis::Network;
+use zk_light_client_lib::aggregation::{
+    check_aggregation_public_values, prove_aggregation, AGGREGATION_ELF,
+};
+use zk_light_client_lib::artifact::{load_proof, save_proof, ProofMetadata, ProofSystem};
+use zk_light_client_lib::{keys, proof, to_hex_string, vkey};
+
+use clap::{Parser, ValueEnum};
+use sp1_sdk::{HashableKey, ProverClient};
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum System {
+    Groth16,
+    Plonk,
+}
+
+/// Wraps compressed proofs saved by `prove` or `aggregate` into a single Groth16 or PLONK proof
+/// verifiable on-chain. The proofs are aggregated by the aggregation program, which verifies them
+/// recursively, and the aggregation proof is verified by `ZkLightClient.updateAggregated`.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Compressed proofs of the main program to wrap, a single proof or consecutive chunks each
+    /// anchored on the tip of the previous one
+    #[arg(required = true)]
+    proofs: Vec<PathBuf>,
+
+    /// Proof system of the wrapped proof
+    #[arg(long, value_enum, default_value = "groth16")]
+    system: System,
+
+    /// Directory of the wrapped proof, next to the first compressed proof by default
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+}
+
+fn main() {
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
+
+    // Parse command line arguments
+    let args = Args::parse();
+    let proof_system = match args.system {
+        System::Groth16 => ProofSystem::Groth16,
+        System::Plonk => ProofSystem::Plonk,
+    };
+
+    // Setup the prover client, the main program verifying key checks the compressed proofs.
+    let client = ProverClient::new();
+    let (_pk, vk) = keys::setup(&client, proof::MAIN_ELF);
+    vkey::assert_pinned_vkey("main", &vk);
+    let (aggregation_pk, aggregation_vk) = keys::setup(&client, AGGREGATION_ELF);
+    vkey::assert_pinned_vkey("aggregation", &aggregation_vk);
+
+    let (mut chunks, mut proofs) = (vec![], vec![]);
+    for proof_path in args.proofs.iter() {
+        let (compressed_proof, metadata, _) = load_proof(proof_path);
+        assert_eq!(
+            metadata.proof_system,
+            ProofSystem::Compressed,
+            "{} is not a compressed proof",
+            proof_path.display()
+        );
+        assert_eq!(
+            metadata.vkey_hash,
+            vk.bytes32(),
+            "{} was not generated by the main program",
+            proof_path.display()
+        );
+        chunks.push(
+            metadata
+                .aggregated_chunk(&compressed_proof)
+                .expect("invalid public values"),
+        );
+        proofs.push(compressed_proof);
+    }
+
+    let proof = prove_aggregation(&client, &aggregation_pk, chunks, &proofs, &vk, proof_system);
+    client
+        .verify(&proof, &aggregation_vk)
+        .expect("failed to verify proof");
+    let (public_values, chunk_count) =
+        check_aggregation_public_values(&proof, &vk, Network::Bitcoin)
+            .expect("invalid public values");
+    println!(
+        "Wrapped {chunk_count} proofs of blocks {} to {} into a {:?} proof",
+        public_values.safe_block_height, public_values.tip_block_height, args.system
+    );
+
+    let output_dir = args
+        .output_dir
+        .unwrap_or_else(|| args.proofs[0].parent().unwrap().to_path_buf());
+    let name = format!(
+        "{}-{}",
+        public_values.safe_block_height, public_values.tip_block_height
+    );
+    let wrapped_path = save_proof(
+        &output_dir,
+        &name,
+        &proof,
+        &ProofMetadata::new(&public_values, &[], &proof, &aggregation_vk),
+    );
+    println!("Proof saved to {}", wrapped_path.display());
+    println!(
+        "Public Inputs: {:?}",
+        to_hex_string(proof.public_values.to_vec().as_slice())
+    );
+    println!("Solidity Ready Proof: {:?}", to_hex_string(&proof.bytes()));
+}
@@ -0,0 +1,490 @@
+//! Verifies Solidity-ready proofs with the SP1 verifier and the reference light client contract
+//! in an in-process EVM, and reports the gas used. The contracts are compiled with `forge build`
+//! on the first run.
+//!
+//! `test_evm_mock` runs with mock proofs by default, the real proofs are opt-in:
+//! `cargo test --release --test evm_test -- --ignored --nocapture`.
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use zk_light_client_core::aggregation::AggregationPublicValues;
+#[cfg(not(feature = "checkpoints"))]
+use zk_light_client_core::commitment::CommitmentScheme;
+use zk_light_client_core::constants::RULE_CHECKPOINTS;
+use zk_light_client_core::genesis::Network;
+use zk_light_client_core::{Anchor, CircuitInput, CircuitPublicValues, DomainTag, ProgramInput};
+use zk_light_client_lib::aggregation::{check_aggregation_public_values, AGGREGATION_ELF};
+use zk_light_client_lib::artifact::{load_proof, ProofSystem};
+use zk_light_client_lib::headers::get_circuit_input;
+#[cfg(not(feature = "checkpoints"))]
+use zk_light_client_lib::headers::mine_regtest_headers;
+use zk_light_client_lib::{keys, proof, vkey};
+
+use alloy_sol_types::{sol, SolCall, SolConstructor, SolError, SolType};
+use revm::db::{CacheDB, EmptyDB};
+use revm::primitives::{Address, Bytes, ExecutionResult, Output, TxKind};
+use revm::Evm;
+use sp1_sdk::{
+    HashableKey, ProverClient, SP1ProofWithPublicValues, SP1ProvingKey, SP1Stdin, SP1VerifyingKey,
+};
+
+sol! {
+    contract ZkLightClient {
+        constructor(
+            address verifier,
+            bytes32 programVKey,
+            bytes32 aggregationVKey,
+            bytes32 programVKeyDigest,
+            bytes4 networkMagic,
+            uint32 programVersion,
+            uint32 ruleFlags,
+            bytes32 trustedBlockHash,
+            uint64 trustedBlockHeight,
+            bytes32 trustedEpochStateHash,
+            bytes32 trustedMmrRoot
+        );
+        function update(bytes calldata publicValues, bytes calldata proofBytes) external;
+        function updateAggregated(bytes calldata publicValues, bytes calldata proofBytes) external;
+        function tipBlockHash() external view returns (bytes32);
+        function mmrRoot() external view returns (bytes32);
+    }
+}
+
+const GAS_LIMIT: u64 = 30_000_000;
+const DEPLOYER: Address = Address::repeat_byte(0x01);
+
+// the safe block is `SAFE_BLOCK_INDEX` blocks later
+const FIRST_BLOCK_HEIGHT: u64 = 854373;
+// blocks of the generated proofs, the safe block included
+const BLOCKS: usize = 10;
+const DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/data");
+const CONTRACTS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../contracts");
+
+// `forge build` artifacts of `contracts`, built once for all the tests
+fn contracts_out() -> &'static Path {
+    static CONTRACTS_OUT: OnceLock<PathBuf> = OnceLock::new();
+    CONTRACTS_OUT.get_or_init(|| {
+        let status = Command::new("forge")
+            .arg("build")
+            .current_dir(CONTRACTS_DIR)
+            .status()
+            .expect("Failed to run `forge build`, is Foundry installed?");
+        assert!(
+            status.success(),
+            "`forge build` failed, run `forge install succinctlabs/sp1-contracts@v2.0.0 \
+             --no-commit` in contracts first"
+        );
+        Path::new(CONTRACTS_DIR).join("out")
+    })
+}
+
+// creation bytecode of a contract compiled by `forge build`
+fn load_bytecode(source: &str, contract: &str) -> Vec<u8> {
+    let path = contracts_out()
+        .join(source)
+        .join(format!("{contract}.json"));
+    let artifact: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(&path).unwrap_or_else(|_| panic!("Missing {}", path.display())),
+    )
+    .expect("Failed to parse contract artifact");
+    let bytecode = artifact["bytecode"]["object"]
+        .as_str()
+        .expect("Contract artifact has no bytecode");
+    hex::decode(bytecode.trim_start_matches("0x")).expect("Failed to parse bytecode")
+}
+
+// In-process EVM with a single sender, gas is free
+struct TestEvm {
+    evm: Evm<'static, (), CacheDB<EmptyDB>>,
+}
+
+impl TestEvm {
+    fn new() -> Self {
+        Self {
+            evm: Evm::builder()
+                .with_db(CacheDB::new(EmptyDB::default()))
+                .build(),
+        }
+    }
+
+    // runs a transaction, returns its gas and output, or its gas and revert reason
+    fn transact(
+        &mut self,
+        transact_to: TxKind,
+        data: Vec<u8>,
+    ) -> Result<(u64, Output), (u64, String)> {
+        let tx = self.evm.tx_mut();
+        tx.caller = DEPLOYER;
+        tx.transact_to = transact_to;
+        tx.data = Bytes::from(data);
+        tx.gas_limit = GAS_LIMIT;
+
+        match self.evm.transact_commit().expect("EVM error") {
+            ExecutionResult::Success {
+                gas_used, output, ..
+            } => Ok((gas_used, output)),
+            ExecutionResult::Revert { gas_used, output } => Err((gas_used, revert_reason(&output))),
+            ExecutionResult::Halt { gas_used, reason } => Err((gas_used, format!("{reason:?}"))),
+        }
+    }
+
+    fn deploy(&mut self, bytecode: Vec<u8>, constructor_args: Vec<u8>) -> Address {
+        let mut data = bytecode;
+        data.extend(constructor_args);
+        match self.transact(TxKind::Create, data) {
+            Ok((_, Output::Create(_, Some(address)))) => address,
+            Ok(_) => panic!("Deployment returned no address"),
+            Err((_, reason)) => panic!("Deployment reverted: {reason}"),
+        }
+    }
+
+    fn call(&mut self, to: Address, data: Vec<u8>) -> Result<(u64, Vec<u8>), (u64, String)> {
+        self.transact(TxKind::Call(to), data)
+            .map(|(gas_used, output)| (gas_used, output.into_data().to_vec()))
+    }
+}
+
+// `Error(string)` message of a revert, or its raw data
+fn revert_reason(output: &Bytes) -> String {
+    alloy_sol_types::Revert::abi_decode(output, true)
+        .map(|revert| revert.reason)
+        .unwrap_or_else(|_| format!("0x{}", hex::encode(output)))
+}
+
+fn assert_rejected(name: &str, evm: &mut TestEvm, light_client: Address, data: Vec<u8>) {
+    let (gas_used, reason) = evm
+        .call(light_client, data)
+        .err()
+        .unwrap_or_else(|| panic!("{name} was accepted"));
+    println!("Rejected {name} ({gas_used} gas): {reason}");
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum System {
+    Groth16,
+    Plonk,
+}
+
+// Solidity-ready proof of `input`, a mock one with a mock client
+fn generate_proof(
+    client: &ProverClient,
+    pk: &SP1ProvingKey,
+    input: CircuitInput,
+    system: System,
+) -> SP1ProofWithPublicValues {
+    let mut stdin = SP1Stdin::new();
+    stdin.write(&ProgramInput::Full(input));
+    let prove = client.prove(pk, stdin);
+    match system {
+        System::Groth16 => prove.groth16().run(),
+        System::Plonk => prove.plonk().run(),
+    }
+    .expect("failed to generate proof")
+}
+
+// Main and aggregation program keys, the real ones must match the checked-in vkeys the deployed
+// contracts are pinned to
+fn setup(client: &ProverClient, mock: bool) -> (SP1ProvingKey, SP1VerifyingKey, SP1VerifyingKey) {
+    let (pk, vk) = keys::setup(client, proof::MAIN_ELF);
+    let (_aggregation_pk, aggregation_vk) = keys::setup(client, AGGREGATION_ELF);
+    if !mock {
+        vkey::assert_pinned_vkey("main", &vk);
+        vkey::assert_pinned_vkey("aggregation", &aggregation_vk);
+    }
+    (pk, vk, aggregation_vk)
+}
+
+// Generates a proof of the main program and runs it through the light client
+fn test_generated_proof(system: System, mock: bool) {
+    let client = if mock {
+        ProverClient::mock()
+    } else {
+        ProverClient::new()
+    };
+    let (pk, vk, aggregation_vk) = setup(&client, mock);
+    let input = get_circuit_input(DATA_DIR, FIRST_BLOCK_HEIGHT, BLOCKS, Anchor::Checkpoint);
+    let proof = generate_proof(&client, &pk, input, system);
+    run_light_client(
+        &client,
+        &pk,
+        &vk,
+        &aggregation_vk,
+        &proof,
+        system,
+        mock,
+        false,
+    );
+}
+
+// Verifies `proof` with the SP1 verifier of `system` and a light client trusting its safe block,
+// then checks that replayed, tampered and foreign proofs are rejected
+#[allow(clippy::too_many_arguments)]
+// the regtest proof is skipped by checkpoints builds
+#[cfg_attr(feature = "checkpoints", allow(unused_variables))]
+fn run_light_client(
+    client: &ProverClient,
+    pk: &SP1ProvingKey,
+    vk: &SP1VerifyingKey,
+    aggregation_vk: &SP1VerifyingKey,
+    proof: &SP1ProofWithPublicValues,
+    system: System,
+    mock: bool,
+    aggregated: bool,
+) {
+    let public_values_bytes = proof.public_values.to_vec();
+    let public_values = if aggregated {
+        check_aggregation_public_values(proof, vk, Network::Bitcoin)
+            .expect("invalid public values")
+            .0
+    } else {
+        proof::check_public_values(proof, vk, Network::Bitcoin).expect("invalid public values")
+    };
+    let proof_bytes = proof.bytes();
+    println!(
+        "Verifying a {system:?}{} proof of blocks {} to {}",
+        if mock { " mock" } else { "" },
+        public_values.safe_block_height,
+        public_values.tip_block_height
+    );
+
+    // [1] deploy the SP1 verifier and a light client trusting the safe block of the proof
+    let mut evm = TestEvm::new();
+    let verifier_bytecode = match (mock, system) {
+        (true, _) => load_bytecode("SP1MockVerifier.sol", "SP1MockVerifier"),
+        (false, System::Groth16) => load_bytecode("SP1VerifierGroth16.sol", "SP1Verifier"),
+        (false, System::Plonk) => load_bytecode("SP1VerifierPlonk.sol", "SP1Verifier"),
+    };
+    let verifier = evm.deploy(verifier_bytecode, vec![]);
+    let light_client_bytecode = load_bytecode("ZkLightClient.sol", "ZkLightClient");
+    let domain_tag = DomainTag::new(Network::Bitcoin);
+    let constructor = |domain_tag: DomainTag, trusted: &CircuitPublicValues| {
+        ZkLightClient::constructorCall {
+            verifier,
+            programVKey: vkey::vkey_hash(vk).into(),
+            aggregationVKey: vkey::vkey_hash(aggregation_vk).into(),
+            programVKeyDigest: vkey::vkey_digest(vk).into(),
+            networkMagic: domain_tag.network_magic.into(),
+            programVersion: domain_tag.program_version,
+            ruleFlags: domain_tag.rule_flags,
+            trustedBlockHash: trusted.safe_block_hash.into(),
+            trustedBlockHeight: trusted.safe_block_height,
+            trustedEpochStateHash: trusted.epoch_state_hash.into(),
+            trustedMmrRoot: trusted.prev_mmr_root.into(),
+        }
+        .abi_encode()
+    };
+    let update = |public_values: &[u8], proof_bytes: &[u8]| {
+        ZkLightClient::updateCall {
+            publicValues: public_values.to_vec().into(),
+            proofBytes: proof_bytes.to_vec().into(),
+        }
+        .abi_encode()
+    };
+    // submits the proof under test, through `updateAggregated` for an aggregation proof
+    let submit = |public_values: &[u8], proof_bytes: &[u8]| {
+        if aggregated {
+            ZkLightClient::updateAggregatedCall {
+                publicValues: public_values.to_vec().into(),
+                proofBytes: proof_bytes.to_vec().into(),
+            }
+            .abi_encode()
+        } else {
+            update(public_values, proof_bytes)
+        }
+    };
+
+    // [2] the proof is accepted and moves the tip and the MMR
+    let light_client = evm.deploy(
+        light_client_bytecode.clone(),
+        constructor(domain_tag, &public_values),
+    );
+    let (gas_used, _) = evm
+        .call(light_client, submit(&public_values_bytes, &proof_bytes))
+        .unwrap_or_else(|(_, reason)| panic!("Proof was rejected: {reason}"));
+    let (_, tip_block_hash) = evm
+        .call(
+            light_client,
+            ZkLightClient::tipBlockHashCall {}.abi_encode(),
+        )
+        .unwrap();
+    let tip_block_hash = ZkLightClient::tipBlockHashCall::abi_decode_returns(&tip_block_hash, true)
+        .unwrap()
+        ._0;
+    assert_eq!(
+        tip_block_hash.0, public_values.tip_block_hash,
+        "Tip was not updated"
+    );
+    let (_, mmr_root) = evm
+        .call(light_client, ZkLightClient::mmrRootCall {}.abi_encode())
+        .unwrap();
+    let mmr_root = ZkLightClient::mmrRootCall::abi_decode_returns(&mmr_root, true)
+        .unwrap()
+        ._0;
+    assert_eq!(
+        mmr_root.0, public_values.mmr_root,
+        "MMR root was not updated"
+    );
+    println!("Accepted proof ({gas_used} gas)");
+
+    // [3] replayed, tampered and foreign proofs are rejected
+    assert_rejected(
+        "replayed proof",
+        &mut evm,
+        light_client,
+        submit(&public_values_bytes, &proof_bytes),
+    );
+
+    let light_client = evm.deploy(
+        light_client_bytecode.clone(),
+        constructor(domain_tag, &public_values),
+    );
+    let mut tampered_proof_bytes = proof_bytes.clone();
+    match tampered_proof_bytes.last_mut() {
+        Some(byte) => *byte ^= 1,
+        // the mock verifier only accepts empty proofs
+        None => tampered_proof_bytes.push(0),
+    }
+    assert_rejected(
+        "tampered proof",
+        &mut evm,
+        light_client,
+        submit(&public_values_bytes, &tampered_proof_bytes),
+    );
+
+    // the mock verifier doesn't check the public values against the proof
+    if !mock {
+        let mut tampered_public_values = public_values;
+        tampered_public_values.tip_block_height += 1;
+        let tampered_public_values_bytes = if aggregated {
+            let aggregated_public_values =
+                AggregationPublicValues::abi_decode(&public_values_bytes, true).unwrap();
+            AggregationPublicValues::abi_encode(&AggregationPublicValues {
+                chain: tampered_public_values.abi_public_inputs(),
+                ..aggregated_public_values
+            })
+        } else {
+            tampered_public_values.abi_encode()
+        };
+        assert_rejected(
+            "tampered public values",
+            &mut evm,
+            light_client,
+            submit(&tampered_public_values_bytes, &proof_bytes),
+        );
+    }
+
+    // a light client at the safe block of the proof, but with another MMR
+    let other_mmr_light_client = evm.deploy(
+        light_client_bytecode.clone(),
+        constructor(
+            domain_tag,
+            &CircuitPublicValues {
+                prev_mmr_root: [0xffu8; 32],
+                ..public_values
+            },
+        ),
+    );
+    assert_rejected(
+        "proof of another MMR",
+        &mut evm,
+        other_mmr_light_client,
+        submit(&public_values_bytes, &proof_bytes),
+    );
+
+    let foreign_light_client = evm.deploy(
+        light_client_bytecode.clone(),
+        constructor(
+            DomainTag {
+                rule_flags: domain_tag.rule_flags ^ RULE_CHECKPOINTS,
+                ..domain_tag
+            },
+            &public_values,
+        ),
+    );
+    assert_rejected(
+        "proof of other rule flags",
+        &mut evm,
+        foreign_light_client,
+        submit(&public_values_bytes, &proof_bytes),
+    );
+
+    // a regtest proof of the same system, submitted to a mainnet light client trusting its safe
+    // block so that only the domain differs. The checkpoints are mainnet ones, so a checkpoints
+    // build can't prove another network
+    #[cfg(not(feature = "checkpoints"))]
+    {
+        let (inputs, _) = proof::build_genesis_proof_inputs(
+            Network::Regtest,
+            CommitmentScheme::Sha256,
+            &mine_regtest_headers(BLOCKS),
+        );
+        let regtest_proof = generate_proof(client, pk, inputs[0], system);
+        let regtest_public_values =
+            proof::check_public_values(&regtest_proof, vk, Network::Regtest)
+                .expect("invalid regtest public values");
+        let foreign_light_client = evm.deploy(
+            light_client_bytecode,
+            constructor(domain_tag, &regtest_public_values),
+        );
+        assert_rejected(
+            "proof of another network",
+            &mut evm,
+            foreign_light_client,
+            update(
+                &regtest_proof.public_values.to_vec(),
+                &regtest_proof.bytes(),
+            ),
+        );
+    }
+}
+
+#[test]
+fn test_evm_mock() {
+    test_generated_proof(System::Groth16, true);
+}
+
+#[test]
+#[ignore = "generates a real Groth16 proof"]
+fn test_evm_groth16() {
+    test_generated_proof(System::Groth16, false);
+}
+
+#[test]
+#[ignore = "generates a real PLONK proof"]
+fn test_evm_plonk() {
+    test_generated_proof(System::Plonk, false);
+}
+
+// Aggregation proof saved by `wrap` or `aggregate` at `EVM_TEST_PROOF`, submitted with
+// `updateAggregated`
+#[test]
+#[ignore = "needs an aggregation proof at EVM_TEST_PROOF"]
+fn test_evm_aggregation_proof() {
+    let proof_path =
+        PathBuf::from(std::env::var("EVM_TEST_PROOF").expect("EVM_TEST_PROOF is not set"));
+    let client = ProverClient::new();
+    let (pk, vk, aggregation_vk) = setup(&client, false);
+    let (proof, metadata, _) = load_proof(&proof_path);
+    assert_eq!(
+        metadata.vkey_hash,
+        aggregation_vk.bytes32(),
+        "{} is not an aggregation proof",
+        proof_path.display()
+    );
+    let system = match metadata.proof_system {
+        ProofSystem::Groth16 => System::Groth16,
+        ProofSystem::Plonk => System::Plonk,
+        ProofSystem::Compressed => panic!("Compressed proofs must be wrapped first"),
+    };
+    run_light_client(
+        &client,
+        &pk,
+        &vk,
+        &aggregation_vk,
+        &proof,
+        system,
+        false,
+        true,
+    );
+}
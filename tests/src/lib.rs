@@ -114,3 +114,2339 @@ mod test {
         println!("Compact Representation (bits): 0x{:08X}", compact);
     }
 }
+
+#[cfg(test)]
+mod mmr {
+    use zk_light_client_core::mmr::{assert_mmr, mmr_root, MmrPeaks, MmrProof};
+    use zk_light_client_core::sha256_merkle::Sha256BlockHashes;
+    use zk_light_client_lib::mmr::BlockHashesMmr;
+
+    const FIRST_HEIGHT: u64 = 840000;
+    // leaf counts around powers of two, where peaks merge
+    const LEAF_COUNTS: [u64; 16] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 15, 16, 17, 31, 32, 33, 100];
+
+    fn block_hash(height: u64) -> [u8; 32] {
+        let mut block_hash = [0u8; 32];
+        block_hash[..8].copy_from_slice(&height.to_le_bytes());
+        block_hash[31] = 0xff;
+        block_hash
+    }
+
+    fn build_mmr(leaf_count: u64) -> BlockHashesMmr {
+        let mut mmr = BlockHashesMmr::new(FIRST_HEIGHT);
+        for height in FIRST_HEIGHT..FIRST_HEIGHT + leaf_count {
+            mmr.append(block_hash(height));
+        }
+        mmr
+    }
+
+    // root of the perfect tree over `leaves`, built independently of the MMR
+    fn perfect_tree_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+        let mut level = leaves.to_vec();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| Sha256BlockHashes::hash_nodes(pair[0], pair[1]))
+                .collect();
+        }
+        level[0]
+    }
+
+    #[test]
+    fn test_peak_bagging() {
+        for leaf_count in LEAF_COUNTS {
+            let mmr = build_mmr(leaf_count);
+            let leaves = (FIRST_HEIGHT..FIRST_HEIGHT + leaf_count)
+                .map(|height| Sha256BlockHashes::hash_leaf(block_hash(height)))
+                .collect::<Vec<_>>();
+
+            // one perfect tree per bit of the leaf count, from the highest to the lowest
+            let mut expected_peaks = vec![];
+            let mut start = 0;
+            for height in (0..64).rev() {
+                if leaf_count & (1 << height) != 0 {
+                    expected_peaks.push(perfect_tree_root(&leaves[start..start + (1 << height)]));
+                    start += 1 << height;
+                }
+            }
+
+            assert_eq!(mmr.peaks(), expected_peaks, "{leaf_count} leaves");
+            assert_eq!(mmr.root(), mmr_root(leaf_count, &expected_peaks));
+        }
+    }
+
+    #[test]
+    fn test_append() {
+        let mut mmr = BlockHashesMmr::new(FIRST_HEIGHT);
+        let mut peaks = MmrPeaks::default();
+        let mut roots = vec![];
+        for height in FIRST_HEIGHT..FIRST_HEIGHT + 100 {
+            mmr.append(block_hash(height));
+            peaks.append(block_hash(height));
+
+            // the program only carries the peaks, both must agree on every root
+            assert_eq!(mmr.leaf_count(), height - FIRST_HEIGHT + 1);
+            assert_eq!(mmr.mmr_peaks().peaks(), peaks.peaks());
+            assert_eq!(mmr.root(), peaks.root());
+            roots.push(mmr.root());
+        }
+
+        // every append changes the root, the leaf count is bound in it
+        roots.sort_unstable();
+        roots.dedup();
+        assert_eq!(roots.len(), 100);
+    }
+
+    #[test]
+    fn test_assert_mmr() {
+        let previous = build_mmr(17);
+        let next = build_mmr(40);
+        // the first block hash is the tip of the previous proof, already in the MMR
+        let block_hashes = (FIRST_HEIGHT + 16..FIRST_HEIGHT + 40)
+            .map(block_hash)
+            .collect::<Vec<_>>();
+        assert_mmr(
+            previous.root(),
+            next.root(),
+            &previous.mmr_peaks(),
+            &block_hashes,
+        );
+
+        // an empty MMR starts with the safe block
+        assert_mmr(
+            MmrPeaks::default().root(),
+            next.root(),
+            &MmrPeaks::default(),
+            &(FIRST_HEIGHT..FIRST_HEIGHT + 40)
+                .map(block_hash)
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "MMR root mismatch")]
+    fn test_assert_mmr_skipped_block() {
+        let previous = build_mmr(17);
+        let next = build_mmr(40);
+        let block_hashes = (FIRST_HEIGHT + 17..FIRST_HEIGHT + 40)
+            .map(block_hash)
+            .collect::<Vec<_>>();
+        assert_mmr(
+            previous.root(),
+            next.root(),
+            &previous.mmr_peaks(),
+            &block_hashes,
+        );
+    }
+
+    #[test]
+    fn test_inclusion_proofs() {
+        for leaf_count in LEAF_COUNTS {
+            let mmr = build_mmr(leaf_count);
+            let root = mmr.root();
+            for height in FIRST_HEIGHT..FIRST_HEIGHT + leaf_count {
+                let proof = mmr.prove(height);
+                assert_eq!(proof.leaf_index, height - FIRST_HEIGHT);
+                assert!(
+                    proof.verify(root, block_hash(height)),
+                    "{leaf_count} {height}"
+                );
+
+                // another block, another position, or a stale root
+                assert!(!proof.verify(root, block_hash(height + 1)));
+                let mut moved = proof.clone();
+                moved.leaf_index = (moved.leaf_index + 1) % leaf_count;
+                assert!(leaf_count == 1 || !moved.verify(root, block_hash(height)));
+                assert!(!proof.verify(build_mmr(leaf_count + 1).root(), block_hash(height)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_historical_proofs() {
+        // a block proven early is still provable against the latest root
+        let mut mmr = build_mmr(5);
+        for height in FIRST_HEIGHT + 5..FIRST_HEIGHT + 70 {
+            mmr.append(block_hash(height));
+            assert!(mmr
+                .prove(FIRST_HEIGHT)
+                .verify(mmr.root(), block_hash(FIRST_HEIGHT)));
+            assert!(mmr.prove(height).verify(mmr.root(), block_hash(height)));
+        }
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        for leaf_count in LEAF_COUNTS {
+            let mmr = build_mmr(leaf_count);
+            let mut decoded: BlockHashesMmr =
+                serde_json::from_str(&serde_json::to_string(&mmr).unwrap()).unwrap();
+            assert_eq!(decoded.first_height, FIRST_HEIGHT);
+            assert_eq!(decoded.leaf_count(), leaf_count);
+            assert_eq!(decoded.root(), mmr.root());
+
+            let proof = decoded.prove(FIRST_HEIGHT + leaf_count / 2);
+            let decoded_proof: MmrProof =
+                serde_json::from_str(&serde_json::to_string(&proof).unwrap()).unwrap();
+            assert_eq!(decoded_proof, proof);
+
+            // a reloaded MMR keeps growing like the original one
+            decoded.append(block_hash(FIRST_HEIGHT + leaf_count));
+            assert_eq!(decoded.root(), build_mmr(leaf_count + 1).root());
+        }
+    }
+}
+
+// Real chain data of tests/data: mainnet headers as `block_<height>.hex`, other blocks and
+// transactions named after their network and hash
+#[cfg(test)]
+mod data {
+    use bitcoin::block::Header;
+    use bitcoin::consensus::encode::deserialize;
+    use zk_light_client_core::genesis::Network;
+    use zk_light_client_core::{Anchor, CircuitInput, CircuitPublicValues};
+    use zk_light_client_lib::{load_hex_bytes, AsOptimizedBlock};
+
+    pub const DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data");
+
+    pub fn load(name: &str) -> Vec<u8> {
+        load_hex_bytes(&format!("{DATA_DIR}/{name}.hex"))
+    }
+
+    // mainnet header at `height`
+    pub fn header(height: u64) -> Header {
+        deserialize(&load(&format!("block_{height}"))).unwrap()
+    }
+
+    // hash of the mainnet header at `height`, in RPC byte order as committed by the program
+    pub fn block_hash(height: u64) -> [u8; 32] {
+        header(height)
+            .as_optimized_block(height)
+            .compute_block_hash()
+    }
+
+    // mainnet block with two transactions, the second one spending three outputs to a public key
+    pub const MAINNET_BLOCK: &str =
+        "mainnet_block_00000000b0c5a240b2a61d2e75692224efd4cbecdf6eaf4cc2cf477ca7c270e7";
+
+    pub fn mainnet_block() -> bitcoin::Block {
+        deserialize(&load(MAINNET_BLOCK)).unwrap()
+    }
+
+    // regtest segwit block with a witness commitment, and a transaction spending two P2WPKH
+    // outputs
+    pub const REGTEST_BLOCK: &str =
+        "regtest_block_38c34a52ad8b8a937ca861a2991372eb9b6a0c9b43a71ba4acba3959f570f816";
+    pub const REGTEST_BLOCK_HEIGHT: u64 = 107;
+
+    pub fn regtest_block() -> bitcoin::Block {
+        deserialize(&load(REGTEST_BLOCK)).unwrap()
+    }
+
+    // hash given in RPC byte order
+    pub fn hash(hex: &str) -> [u8; 32] {
+        hex::decode(hex).unwrap().try_into().unwrap()
+    }
+
+    // txids of mainnet block 100000, as listed by `getblock`
+    pub fn block_100000_txids() -> Vec<[u8; 32]> {
+        let txids: Vec<String> = serde_json::from_str(
+            &std::fs::read_to_string(format!("{DATA_DIR}/block_100000_txids.json")).unwrap(),
+        )
+        .unwrap();
+        txids.iter().map(|txid| hash(txid)).collect()
+    }
+
+    // input holding only the header of `block`, which is all the block contents checks read. The
+    // header is not linked to a proven chain
+    pub fn header_input(block: &bitcoin::Block, height: u64, network: Network) -> CircuitInput {
+        CircuitInput::new(
+            CircuitPublicValues {
+                safe_block_height: height,
+                tip_block_height: height,
+                ..Default::default()
+            },
+            vec![block.as_optimized_block(height)],
+            Default::default(),
+            Default::default(),
+            Anchor::default(),
+            network,
+        )
+    }
+}
+
+#[cfg(test)]
+mod safe_block {
+    use crate::data::{block_hash, header};
+    use crypto_bigint::U256;
+    use zk_light_client_core::btc_light_client::{assert_safe_block, Block, EpochState};
+    use zk_light_client_core::genesis::Network;
+    use zk_light_client_lib::proof::build_epoch_state;
+    use zk_light_client_lib::AsOptimizedBlock;
+
+    // mainnet block 1, with the epoch state of the chain ending at it
+    fn block_1() -> (Block, EpochState) {
+        let epoch_state = build_epoch_state(&header(0), 0, &[header(0), header(1)], U256::ZERO);
+        (header(1).as_optimized_block(1), epoch_state)
+    }
+
+    #[test]
+    fn test_mainnet_safe_block() {
+        let (block, epoch_state) = block_1();
+        assert_safe_block(block_hash(1), 1, &block, &epoch_state, Network::Bitcoin);
+    }
+
+    #[test]
+    #[should_panic(expected = "Safe block height mismatch")]
+    fn test_wrong_height() {
+        let (block, epoch_state) = block_1();
+        assert_safe_block(block_hash(1), 2, &block, &epoch_state, Network::Bitcoin);
+    }
+
+    #[test]
+    #[should_panic(expected = "Safe block hash mismatch")]
+    fn test_other_committed_hash() {
+        let (block, epoch_state) = block_1();
+        assert_safe_block(block_hash(2), 1, &block, &epoch_state, Network::Bitcoin);
+    }
+
+    #[test]
+    #[should_panic(expected = "PoW invalid hash < target")]
+    fn test_fabricated_header() {
+        let (mut block, epoch_state) = block_1();
+        // the hash of a header with another nonce doesn't meet the target, whatever is committed
+        block.nonce = [0; 4];
+        let block_hash = block.compute_block_hash();
+        assert_safe_block(block_hash, 1, &block, &epoch_state, Network::Bitcoin);
+    }
+
+    #[test]
+    #[should_panic(expected = "Epoch state does not end with the safe block")]
+    fn test_epoch_state_of_another_block() {
+        let (block, _) = block_1();
+        let epoch_state = build_epoch_state(&header(0), 0, &[header(0)], U256::ZERO);
+        assert_safe_block(block_hash(1), 1, &block, &epoch_state, Network::Bitcoin);
+    }
+}
+
+#[cfg(test)]
+mod epoch_state {
+    use crate::data::header;
+    use bitcoin::hashes::{sha256, Hash};
+    use crypto_bigint::{Encoding, U256};
+    use zk_light_client_core::btc_light_client::EpochState;
+    use zk_light_client_lib::proof::build_epoch_state;
+    use zk_light_client_lib::AsOptimizedBlock;
+
+    // epoch state of the mainnet chain ending at `height`, for the first blocks of the chain
+    fn mainnet_epoch_state(height: u64) -> EpochState {
+        let history = (0..=height).map(header).collect::<Vec<_>>();
+        let chainwork = history
+            .iter()
+            .map(|header| header.work())
+            .reduce(|total, work| total + work)
+            .unwrap();
+        build_epoch_state(
+            &header(0),
+            0,
+            &history,
+            U256::from_be_bytes(chainwork.to_be_bytes()),
+        )
+    }
+
+    #[test]
+    fn test_serialization() {
+        let epoch_state = mainnet_epoch_state(1);
+        let bytes = epoch_state.serialize();
+        assert_eq!(bytes[0..8], 0u64.to_le_bytes());
+        assert_eq!(bytes[8..12], header(0).time.to_le_bytes());
+        assert_eq!(bytes[12..16], header(0).bits.to_consensus().to_le_bytes());
+        // two blocks of 0x100010001 work each
+        assert_eq!(bytes[16..48], U256::from_u64(0x200020002).to_be_bytes());
+        // the history is right-aligned, the missing timestamps are zero
+        assert_eq!(bytes[48..84], [0; 36]);
+        assert_eq!(bytes[84..88], header(0).time.to_le_bytes());
+        assert_eq!(bytes[88..92], header(1).time.to_le_bytes());
+        assert_eq!(
+            epoch_state.compute_state_hash(),
+            sha256::Hash::hash(&bytes).to_byte_array()
+        );
+    }
+
+    #[test]
+    fn test_state_hash_covers_every_field() {
+        let epoch_state = mainnet_epoch_state(1);
+        let mut changed = [epoch_state; 5];
+        changed[0].epoch_start_height += 2016;
+        changed[1].epoch_start_time[0] ^= 1;
+        changed[2].bits[0] ^= 1;
+        changed[3].chainwork[31] ^= 1;
+        changed[4].timestamps[0][0] ^= 1;
+        for other in changed.iter() {
+            assert_ne!(other.compute_state_hash(), epoch_state.compute_state_hash());
+        }
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut epoch_state = mainnet_epoch_state(0);
+        for height in 1..=2 {
+            epoch_state.advance(&header(height).as_optimized_block(height));
+            assert_eq!(epoch_state, mainnet_epoch_state(height));
+        }
+    }
+}
+
+#[cfg(all(test, feature = "checkpoints"))]
+mod checkpoints {
+    use crate::data::{block_hash, header, DATA_DIR};
+    use zk_light_client_core::btc_light_client::EpochState;
+    use zk_light_client_core::checkpoints::{
+        assert_anchor, checkpoints_root, latest_checkpoint, MAINNET_CHECKPOINTS,
+    };
+    use zk_light_client_core::commitment::CommitmentScheme;
+    use zk_light_client_core::genesis::Network;
+    use zk_light_client_core::{validate_block, Anchor, CircuitInput, CircuitPublicValues};
+    use zk_light_client_lib::headers::get_circuit_input;
+    use zk_light_client_lib::mmr::BlockHashesMmr;
+    use zk_light_client_lib::proof::{build_block_proof_input, tip_epoch_state};
+
+    // mainnet blocks 0 and 1 anchored on the genesis checkpoint, then blocks 1 and 2 anchored on
+    // the proven tip of the first range
+    fn proven_ranges() -> (CircuitInput, CircuitInput) {
+        let mut mmr = BlockHashesMmr::new(0);
+        let first = build_block_proof_input(
+            0,
+            &[header(0), header(1)],
+            Network::Bitcoin.genesis_epoch_state(),
+            &mut mmr,
+            Anchor::Checkpoint,
+            Network::Bitcoin,
+            CommitmentScheme::Sha256,
+        );
+        let second = build_block_proof_input(
+            1,
+            &[header(1), header(2)],
+            tip_epoch_state(&first),
+            &mut mmr,
+            Anchor::ProvenTip {
+                vkey_digest: [1; 8],
+                public_values: first.public_values,
+            },
+            Network::Bitcoin,
+            CommitmentScheme::Sha256,
+        );
+        (first, second)
+    }
+
+    fn assert_input_anchor(input: &CircuitInput) {
+        assert_anchor(
+            &input.public_values,
+            &input.blocks[0],
+            &input.epoch_state,
+            &input.anchor,
+        );
+    }
+
+    // `second` anchored on another previous proof
+    fn anchor_on(second: &mut CircuitInput, previous: CircuitPublicValues) {
+        second.anchor = Anchor::ProvenTip {
+            vkey_digest: [1; 8],
+            public_values: previous,
+        };
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(MAINNET_CHECKPOINTS[0].hash, block_hash(0));
+        for checkpoints in MAINNET_CHECKPOINTS.windows(2) {
+            assert!(checkpoints[0].height < checkpoints[1].height);
+        }
+        assert_eq!(latest_checkpoint(100000).height, 0);
+    }
+
+    #[test]
+    fn test_listed_checkpoint() {
+        let (first, _) = proven_ranges();
+        assert_eq!(first.public_values.checkpoints_root, checkpoints_root());
+        assert_input_anchor(&first);
+        validate_block(first);
+    }
+
+    #[test]
+    fn test_scripts_anchor_on_a_checkpoint() {
+        // the range of the scripts is moved down to the latest checkpoint below its safe block
+        let input = get_circuit_input(DATA_DIR, 99990, 3, Anchor::Checkpoint);
+        assert_eq!(input.public_values.safe_block_hash, block_hash(0));
+        assert_eq!(input.public_values.tip_block_hash, block_hash(2));
+        validate_block(input);
+    }
+
+    #[test]
+    #[should_panic(expected = "Safe block is not a checkpoint")]
+    fn test_unlisted_checkpoint() {
+        let (_, mut second) = proven_ranges();
+        second.anchor = Anchor::Checkpoint;
+        second.public_values.anchor_vkey_digest = [0; 32];
+        assert_input_anchor(&second);
+    }
+
+    #[test]
+    #[should_panic(expected = "Epoch state does not match checkpoint")]
+    fn test_checkpoint_epoch_state() {
+        let (mut first, _) = proven_ranges();
+        first.epoch_state = EpochState::default();
+        assert_input_anchor(&first);
+    }
+
+    #[test]
+    fn test_proven_tip() {
+        let (_, second) = proven_ranges();
+        assert_input_anchor(&second);
+        validate_block(second);
+    }
+
+    #[test]
+    #[should_panic(expected = "Safe block is not the previous proof tip")]
+    fn test_proven_tip_hash_mismatch() {
+        let (first, mut second) = proven_ranges();
+        let mut previous = first.public_values;
+        previous.tip_block_hash = block_hash(2);
+        anchor_on(&mut second, previous);
+        assert_input_anchor(&second);
+    }
+
+    #[test]
+    #[should_panic(expected = "Epoch state is not the previous proof tip state")]
+    fn test_proven_tip_epoch_state_mismatch() {
+        let (first, mut second) = proven_ranges();
+        let mut previous = first.public_values;
+        previous.tip_epoch_state_hash = first.public_values.epoch_state_hash;
+        anchor_on(&mut second, previous);
+        assert_input_anchor(&second);
+    }
+
+    #[test]
+    #[should_panic(expected = "Previous MMR root is not the previous proof MMR root")]
+    fn test_proven_tip_mmr_mismatch() {
+        let (first, mut second) = proven_ranges();
+        let mut previous = first.public_values;
+        previous.mmr_root = [0; 32];
+        anchor_on(&mut second, previous);
+        assert_input_anchor(&second);
+    }
+
+    #[test]
+    #[should_panic(expected = "Previous proof trusted a different checkpoint set")]
+    fn test_proven_tip_checkpoints_mismatch() {
+        let (first, mut second) = proven_ranges();
+        let mut previous = first.public_values;
+        previous.checkpoints_root = [0; 32];
+        anchor_on(&mut second, previous);
+        assert_input_anchor(&second);
+    }
+
+    #[test]
+    #[should_panic(expected = "Chained proofs are not anchored")]
+    fn test_chained_with_checkpoints_root() {
+        let (_, mut second) = proven_ranges();
+        second.anchor = Anchor::Chained;
+        second.public_values.anchor_vkey_digest = [0; 32];
+        assert_input_anchor(&second);
+    }
+
+    #[test]
+    #[should_panic(expected = "Chained proofs are not anchored")]
+    fn test_chained_with_anchor_vkey() {
+        let (_, mut second) = proven_ranges();
+        second.anchor = Anchor::Chained;
+        second.public_values.checkpoints_root = [0; 32];
+        assert_input_anchor(&second);
+    }
+}
+
+#[cfg(test)]
+mod consensus {
+    use crate::data::header;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{CompactTarget, Target};
+    use crypto_bigint::{Encoding, U256};
+    use zk_light_client_core::btc_light_client::{
+        assert_target_bits, bits_to_target, target_to_bits, verify_block, AsLittleEndianBytes,
+        Block, EpochState,
+    };
+    use zk_light_client_core::commitment::CommitmentScheme;
+    use zk_light_client_core::constants::EXPECTED_EPOCH_SECONDS;
+    use zk_light_client_core::genesis::{assert_genesis, Network};
+    use zk_light_client_core::validate_block;
+    use zk_light_client_lib::headers::mine_regtest_headers;
+    use zk_light_client_lib::proof::build_genesis_proof_inputs;
+    use zk_light_client_lib::AsOptimizedBlock;
+
+    const NETWORKS: [(Network, bitcoin::Network); 4] = [
+        (Network::Bitcoin, bitcoin::Network::Bitcoin),
+        (Network::Testnet, bitcoin::Network::Testnet),
+        (Network::Signet, bitcoin::Network::Signet),
+        (Network::Regtest, bitcoin::Network::Regtest),
+    ];
+
+    // xorshift64, the cases are the same on every run
+    struct Cases(u64);
+
+    impl Cases {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        // normalized compact encoding of a target at most the mainnet PoW limit
+        fn bits(&mut self) -> u32 {
+            let exponent = 3 + self.next() % 27;
+            let mantissa = 0x8000 + self.next() % 0x7f8000;
+            let bits = (exponent << 24 | mantissa) as u32;
+            let target = Target::from_compact(CompactTarget::from_consensus(bits));
+            target
+                .min(Target::MAX_ATTAINABLE_MAINNET)
+                .to_compact_lossy()
+                .to_consensus()
+        }
+    }
+
+    // block mined on top of `previous`, with the given time and bits
+    fn mine(previous: &Block, time: u32, bits: u32) -> Block {
+        let mut block = Block {
+            height: previous.height + 1,
+            prev_blockhash: previous.compute_block_hash().to_little_endian(),
+            time: time.to_le_bytes(),
+            bits: bits.to_le_bytes(),
+            ..*previous
+        };
+        let target = bits_to_target(block.bits);
+        while U256::from_be_slice(&block.compute_block_hash()) > target {
+            block.nonce = (u32::from_le_bytes(block.nonce) + 1).to_le_bytes();
+        }
+        block
+    }
+
+    #[test]
+    fn test_genesis_blocks() {
+        for (network, bitcoin_network) in NETWORKS {
+            let genesis = bitcoin::blockdata::constants::genesis_block(bitcoin_network);
+            assert_eq!(
+                network.genesis_block().serialize(),
+                genesis.as_optimized_block(0).serialize()
+            );
+            assert_eq!(
+                network.genesis_block().compute_block_hash(),
+                genesis.block_hash().to_byte_array().to_little_endian()
+            );
+            assert_eq!(network.magic(), bitcoin_network.magic().to_bytes());
+            assert_eq!(
+                network.pow_limit_bits(),
+                genesis.header.bits.to_consensus().to_le_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn test_mainnet_genesis_mode() {
+        let (inputs, mmr) = build_genesis_proof_inputs(
+            Network::Bitcoin,
+            CommitmentScheme::Sha256,
+            &[header(0), header(1), header(2)],
+        );
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(mmr.leaf_count(), 3);
+        validate_block(inputs[0]);
+    }
+
+    // the checkpoints are mainnet ones, a checkpoints build can't anchor another network
+    #[cfg(not(feature = "checkpoints"))]
+    #[test]
+    fn test_regtest_genesis_mode() {
+        // the batches go past a retarget height, where regtest keeps its target
+        let headers = mine_regtest_headers(2100);
+        let (inputs, _) =
+            build_genesis_proof_inputs(Network::Regtest, CommitmentScheme::Keccak256, &headers);
+        assert_eq!(inputs.len(), 5);
+        for input in inputs {
+            validate_block(input);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "First block is not the genesis block")]
+    fn test_genesis_mode_of_another_network() {
+        build_genesis_proof_inputs(
+            Network::Testnet,
+            CommitmentScheme::Sha256,
+            &[header(0), header(1)],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Epoch state is not the genesis epoch state")]
+    fn test_genesis_epoch_state() {
+        let (inputs, _) = build_genesis_proof_inputs(
+            Network::Bitcoin,
+            CommitmentScheme::Sha256,
+            &[header(0), header(1)],
+        );
+        let mut epoch_state = inputs[0].epoch_state;
+        epoch_state.epoch_start_time = [0; 4];
+        assert_genesis(&inputs[0].public_values, &epoch_state, Network::Bitcoin);
+    }
+
+    #[test]
+    fn test_median_time_past() {
+        // only the known timestamps count
+        let genesis_epoch_state = Network::Bitcoin.genesis_epoch_state();
+        assert_eq!(genesis_epoch_state.median_time_past(), header(0).time);
+
+        let mut epoch_state = EpochState::default();
+        for time in [5u32, 1, 4, 2] {
+            epoch_state.accumulate(&Block {
+                time: time.to_le_bytes(),
+                ..Default::default()
+            });
+        }
+        assert_eq!(epoch_state.median_time_past(), 4);
+        for time in 6..=12u32 {
+            epoch_state.accumulate(&Block {
+                time: time.to_le_bytes(),
+                ..Default::default()
+            });
+        }
+        assert_eq!(epoch_state.timestamps[0], 5u32.to_le_bytes());
+        assert_eq!(epoch_state.median_time_past(), 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "Block time is not greater than median time past")]
+    fn test_block_at_median_time_past() {
+        // block 1 at the genesis time, which is the median time past of genesis alone
+        let genesis = Network::Bitcoin.genesis_block();
+        let mut block = header(1).as_optimized_block(1);
+        block.time = genesis.time;
+        verify_block(
+            genesis.compute_block_hash(),
+            &block,
+            &Network::Bitcoin.genesis_epoch_state(),
+            0,
+            Network::Bitcoin,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Target is easier than the PoW limit")]
+    fn test_target_above_pow_limit() {
+        // regtest blocks are easier than the mainnet PoW limit
+        let headers = mine_regtest_headers(2);
+        let mut epoch_state = Network::Regtest.genesis_epoch_state();
+        epoch_state.bits = headers[1].bits.to_consensus().to_le_bytes();
+        verify_block(
+            headers[0].block_hash().to_byte_array().to_little_endian(),
+            &headers[1].as_optimized_block(1),
+            &epoch_state,
+            0,
+            Network::Bitcoin,
+        );
+    }
+
+    // regtest genesis with an epoch target harder than the PoW limit, and its epoch state
+    fn hard_regtest_epoch() -> (Block, EpochState) {
+        let genesis = Network::Regtest.genesis_block();
+        let mut epoch_state = Network::Regtest.genesis_epoch_state();
+        epoch_state.bits = 0x1f00ffffu32.to_le_bytes();
+        (genesis, epoch_state)
+    }
+
+    #[test]
+    fn test_min_difficulty_block() {
+        let (genesis, epoch_state) = hard_regtest_epoch();
+        let genesis_time = u32::from_le_bytes(genesis.time);
+        let pow_limit = u32::from_le_bytes(Network::Regtest.pow_limit_bits());
+
+        // more than 20 minutes after its parent, a block may be mined at the PoW limit
+        let block = mine(&genesis, genesis_time + 1201, pow_limit);
+        let hash = genesis.compute_block_hash();
+        verify_block(hash, &block, &epoch_state, 0, Network::Regtest);
+        // and always at the epoch target
+        let block = mine(&genesis, genesis_time + 600, 0x1f00ffff);
+        verify_block(hash, &block, &epoch_state, 0, Network::Regtest);
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposed target does not match real target")]
+    fn test_early_min_difficulty_block() {
+        let (genesis, epoch_state) = hard_regtest_epoch();
+        let genesis_time = u32::from_le_bytes(genesis.time);
+        let pow_limit = u32::from_le_bytes(Network::Regtest.pow_limit_bits());
+        let block = mine(&genesis, genesis_time + 1200, pow_limit);
+        let hash = genesis.compute_block_hash();
+        verify_block(hash, &block, &epoch_state, 0, Network::Regtest);
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposed target does not match real target")]
+    fn test_mainnet_min_difficulty_block() {
+        let genesis = Network::Bitcoin.genesis_block();
+        let mut epoch_state = Network::Bitcoin.genesis_epoch_state();
+        epoch_state.bits = 0x1c00ffffu32.to_le_bytes();
+        verify_block(
+            genesis.compute_block_hash(),
+            &header(1).as_optimized_block(1),
+            &epoch_state,
+            0,
+            Network::Bitcoin,
+        );
+    }
+
+    #[test]
+    fn test_target_bits_against_rust_bitcoin() {
+        let mut cases = Cases(0x2545f4914f6cdd1d);
+        for _ in 0..5000 {
+            let last_bits = cases.bits();
+            let epoch_start_time = 1_500_000_000 + cases.next() as u32 % 100_000_000;
+            // timespans within and beyond the factor 4 bounds
+            let timespan = cases.next() % (6 * EXPECTED_EPOCH_SECONDS as u64);
+            let next_bits = CompactTarget::from_next_work_required(
+                CompactTarget::from_consensus(last_bits),
+                timespan,
+                bitcoin::Network::Bitcoin,
+            );
+
+            let last_epoch = EpochState {
+                epoch_start_time: epoch_start_time.to_le_bytes(),
+                bits: last_bits.to_le_bytes(),
+                ..Default::default()
+            };
+            let last_epoch_end_block = Block {
+                time: (epoch_start_time + timespan as u32).to_le_bytes(),
+                bits: last_bits.to_le_bytes(),
+                ..Default::default()
+            };
+            let new_epoch_begin_block = Block {
+                bits: next_bits.to_consensus().to_le_bytes(),
+                ..Default::default()
+            };
+            assert_target_bits(
+                &last_epoch,
+                &last_epoch_end_block,
+                &new_epoch_begin_block,
+                Network::Bitcoin,
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "New epoch target bits mismatch")]
+    fn test_wrong_target_bits() {
+        let last_bits = 0x1d00ffffu32;
+        let last_epoch = EpochState {
+            bits: last_bits.to_le_bytes(),
+            ..Default::default()
+        };
+        // an epoch twice as fast halves the target
+        let last_epoch_end_block = Block {
+            time: (EXPECTED_EPOCH_SECONDS / 2).to_le_bytes(),
+            bits: last_bits.to_le_bytes(),
+            ..Default::default()
+        };
+        let new_epoch_begin_block = Block {
+            bits: last_bits.to_le_bytes(),
+            ..Default::default()
+        };
+        assert_target_bits(
+            &last_epoch,
+            &last_epoch_end_block,
+            &new_epoch_begin_block,
+            Network::Bitcoin,
+        );
+    }
+
+    #[test]
+    fn test_target_to_bits_against_rust_bitcoin() {
+        let mut cases = Cases(0x9e3779b97f4a7c15);
+        for _ in 0..20000 {
+            let mut bytes = [0u8; 32];
+            for chunk in bytes.chunks_exact_mut(8) {
+                chunk.copy_from_slice(&cases.next().to_be_bytes());
+            }
+            // targets of every size, down to zero
+            let leading_zeros = cases.next() as usize % 33;
+            bytes[..leading_zeros].fill(0);
+            assert_eq!(
+                u32::from_le_bytes(target_to_bits(U256::from_be_bytes(bytes))),
+                Target::from_be_bytes(bytes)
+                    .to_compact_lossy()
+                    .to_consensus(),
+                "{bytes:02x?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_compact_round_trip() {
+        for bits in [
+            0x1d00ffffu32,
+            0x1b04864c,
+            0x17034219,
+            0x1e0377ae,
+            0x207fffff,
+            0x03123456,
+        ] {
+            assert_eq!(
+                target_to_bits(bits_to_target(bits.to_le_bytes())),
+                bits.to_le_bytes()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod header_conversions {
+    use crate::data::{header, mainnet_block};
+    use bitcoin::consensus::encode::serialize;
+    use crypto_bigint::{Encoding, U256};
+    use zk_light_client_core::btc_light_client::block_work;
+    use zk_light_client_core::commitment::CommitmentScheme;
+    use zk_light_client_core::genesis::Network;
+    use zk_light_client_core::{validate_block, Anchor};
+    use zk_light_client_lib::headers::{
+        get_regtest_circuit_input, load_chain, mine_regtest_headers, SAFE_BLOCK_INDEX,
+    };
+    use zk_light_client_lib::mmr::BlockHashesMmr;
+    use zk_light_client_lib::proof::build_block_proof_input;
+    use zk_light_client_lib::{to_hex_string, AsBitcoinHeader, AsOptimizedBlock};
+
+    #[test]
+    fn test_mainnet_headers() {
+        for height in [0, 1, 2, 100000] {
+            let header = header(height);
+            let raw: [u8; 80] = serialize(&header).try_into().unwrap();
+            let block = header.as_optimized_block(height);
+            assert_eq!(block.height, height);
+            assert_eq!(block.serialize(), raw);
+            assert_eq!(block.as_bitcoin_header(), header);
+            assert_eq!(raw.as_optimized_block(height).serialize(), raw);
+            assert_eq!(raw.as_optimized_block(height).as_bitcoin_header(), header);
+            assert_eq!(raw.time(), header.time());
+        }
+    }
+
+    #[test]
+    fn test_full_block() {
+        let block = mainnet_block();
+        assert_eq!(
+            block.as_optimized_block(7).serialize(),
+            block.header.as_optimized_block(7).serialize()
+        );
+        assert_eq!(
+            block.as_optimized_block(7).as_bitcoin_header(),
+            block.header
+        );
+        assert_eq!(block.time(), block.header.time.to_le_bytes());
+    }
+
+    #[test]
+    fn test_load_chain_chainwork() {
+        let headers = mine_regtest_headers(16);
+        let data_dir = std::env::temp_dir().join(format!("regtest-headers-{}", std::process::id()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+        for (height, header) in headers.iter().enumerate() {
+            std::fs::write(
+                data_dir.join(format!("block_{height}.hex")),
+                to_hex_string(&serialize(header)),
+            )
+            .unwrap();
+        }
+        let (safe_block_height, chain, epoch_state) = load_chain(data_dir.to_str().unwrap(), 0, 5);
+        std::fs::remove_dir_all(&data_dir).unwrap();
+        assert_eq!(safe_block_height, 10);
+        assert_eq!(chain, headers[10..15]);
+
+        // the chainwork counts the safe block, as for an anchored chain
+        let work = block_work(headers[10].bits.to_consensus().to_le_bytes());
+        assert_eq!(epoch_state.chainwork, work.to_be_bytes());
+        let input = build_block_proof_input(
+            safe_block_height,
+            &chain,
+            epoch_state,
+            &mut BlockHashesMmr::new(safe_block_height),
+            Anchor::Chained,
+            Network::Regtest,
+            CommitmentScheme::Sha256,
+        );
+        assert_eq!(
+            input.public_values.tip_chainwork,
+            work.wrapping_mul(&U256::from_u8(5)).to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn test_regtest_circuit_input() {
+        let input = get_regtest_circuit_input(20);
+        let public_values = validate_block(input);
+        assert_eq!(public_values.safe_block_height, SAFE_BLOCK_INDEX as u64);
+        assert_eq!(public_values.tip_block_height, SAFE_BLOCK_INDEX as u64 + 19);
+    }
+}
+
+#[cfg(test)]
+mod flyclient {
+    use bitcoin::hashes::{sha256, Hash};
+    use crypto_bigint::{Encoding, U256};
+    use zk_light_client_core::btc_light_client::block_work;
+    use zk_light_client_core::constants::{FLYCLIENT_SAMPLES, FLYCLIENT_SUFFIX_BLOCKS};
+    use zk_light_client_core::flyclient::{
+        flyclient_sample_point, flyclient_seed, validate_flyclient, FlyClientInput,
+    };
+    use zk_light_client_core::genesis::Network;
+    use zk_light_client_core::Anchor;
+    use zk_light_client_lib::flyclient::build_flyclient_proof_input;
+    use zk_light_client_lib::headers::mine_regtest_headers;
+    use zk_light_client_lib::proof::build_epoch_state;
+
+    const SAFE_BLOCK_HEIGHT: u64 = 10;
+
+    // a regtest chain crossing an epoch boundary, on top of block 10
+    fn input() -> FlyClientInput {
+        let headers = mine_regtest_headers(2400);
+        let safe_block = &headers[SAFE_BLOCK_HEIGHT as usize];
+        let epoch_state = build_epoch_state(
+            &headers[0],
+            0,
+            &headers[..=SAFE_BLOCK_HEIGHT as usize],
+            block_work(safe_block.bits.to_consensus().to_le_bytes()),
+        );
+        build_flyclient_proof_input(
+            SAFE_BLOCK_HEIGHT,
+            &headers[SAFE_BLOCK_HEIGHT as usize..],
+            epoch_state,
+            Anchor::Chained,
+            Network::Regtest,
+        )
+    }
+
+    #[test]
+    fn test_seed() {
+        let tip_block_hash = [1u8; 32];
+        let mmr_root = [2u8; 32];
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&tip_block_hash);
+        bytes.extend_from_slice(&mmr_root);
+        bytes.extend_from_slice(&2400u64.to_be_bytes());
+        let seed = flyclient_seed(tip_block_hash, mmr_root, 2400);
+        assert_eq!(seed, sha256::Hash::hash(&bytes).to_byte_array());
+
+        assert_ne!(flyclient_seed([3u8; 32], mmr_root, 2400), seed);
+        assert_ne!(flyclient_seed(tip_block_hash, [3u8; 32], 2400), seed);
+        assert_ne!(flyclient_seed(tip_block_hash, mmr_root, 2401), seed);
+    }
+
+    #[test]
+    fn test_sample_points_in_range() {
+        let starts = [U256::ZERO, U256::from_u64(12345), U256::MAX >> 1];
+        let widths = [
+            U256::ONE,
+            U256::from_u8(2),
+            U256::from_u8(3),
+            U256::from_u64(1000),
+            U256::ONE << 200,
+            U256::MAX >> 2,
+        ];
+        for (i, (start, width)) in starts
+            .iter()
+            .flat_map(|start| widths.iter().map(move |width| (start, width)))
+            .enumerate()
+        {
+            let end = start.wrapping_add(width);
+            for leaf_count in [2, 3, 2400, u64::MAX] {
+                let seed = flyclient_seed([i as u8; 32], [0u8; 32], leaf_count);
+                for index in 0..FLYCLIENT_SAMPLES {
+                    let point = flyclient_sample_point(seed, index, *start, end, leaf_count);
+                    assert!(*start <= point && point < end);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_points_towards_the_tip() {
+        // 20 buckets, the first one is the older half of the work
+        let leaf_count = 1 << 20;
+        let width = U256::from_u64(1 << 40);
+        let seed = flyclient_seed([0u8; 32], [0u8; 32], leaf_count);
+        let points = (0..FLYCLIENT_SAMPLES)
+            .map(|index| flyclient_sample_point(seed, index, U256::ZERO, width, leaf_count))
+            .collect::<Vec<_>>();
+        let older_half = points.iter().filter(|&&point| point < width >> 1).count();
+        let last_bucket = points
+            .iter()
+            .filter(|&&point| point >= width.wrapping_sub(&(width >> 19)))
+            .count();
+        assert!(0 < older_half && older_half < FLYCLIENT_SAMPLES / 4);
+        assert!(last_bucket > 0);
+    }
+
+    #[test]
+    fn test_valid_input() {
+        let input = input();
+        assert_eq!(input.samples.len(), FLYCLIENT_SAMPLES);
+        assert_eq!(input.suffix.headers.len(), FLYCLIENT_SUFFIX_BLOCKS as usize);
+        // samples after the epoch boundary are verified from its start
+        assert!(input
+            .samples
+            .iter()
+            .any(|sample| sample.epoch_start.is_some()));
+
+        let public_values = validate_flyclient(input.clone());
+        assert_eq!(public_values.abi_encode(), input.public_values.abi_encode());
+        assert_eq!(public_values.tip_block_height, 2399);
+        let work = block_work(input.safe_block.block.bits);
+        assert_eq!(
+            public_values.tip_chainwork,
+            work.wrapping_mul(&U256::from_u64(2390)).to_be_bytes()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "FlyClient header is not committed")]
+    fn test_sample_work_mismatch() {
+        let mut input = input();
+        let sampled = input.samples[0].headers.last_mut().unwrap();
+        sampled.proof.siblings[0].work[31] ^= 1;
+        validate_flyclient(input);
+    }
+
+    #[test]
+    #[should_panic(expected = "FlyClient header is not committed")]
+    fn test_sample_hash_mismatch() {
+        let mut input = input();
+        let sampled = input.samples[0].headers.last_mut().unwrap();
+        sampled.proof.siblings[0].hash[0] ^= 1;
+        validate_flyclient(input);
+    }
+
+    #[test]
+    #[should_panic(expected = "FlyClient leaf index does not match block height")]
+    fn test_sample_proof_of_another_header() {
+        let mut input = input();
+        let other_proof = input.suffix.headers[0].proof.clone();
+        input.samples[0].headers.last_mut().unwrap().proof = other_proof;
+        validate_flyclient(input);
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposed prev_block hash does not match real prev_block hash")]
+    fn test_broken_suffix() {
+        let mut input = input();
+        input.suffix.headers[50].block.prev_blockhash[0] ^= 1;
+        validate_flyclient(input);
+    }
+
+    #[test]
+    #[should_panic(expected = "FlyClient suffix is too short")]
+    fn test_truncated_suffix() {
+        let mut input = input();
+        input.suffix.headers.pop();
+        validate_flyclient(input);
+    }
+}
+
+#[cfg(test)]
+mod aggregation {
+    use alloy_sol_types::SolType;
+    use bitcoin::block::Header;
+    use zk_light_client_core::aggregation::{
+        aggregate, AggregatedChunk, AggregationInput, AggregationPublicValues,
+    };
+    use zk_light_client_core::btc_light_client::{block_work, EpochState};
+    use zk_light_client_core::commitment::CommitmentScheme;
+    use zk_light_client_core::constants::MAX_BLOCKS;
+    use zk_light_client_core::genesis::Network;
+    use zk_light_client_core::{
+        validate_block, vkey_digest_to_bytes, Anchor, CircuitInput, CircuitPublicValues, DomainTag,
+    };
+    use zk_light_client_lib::headers::{mine_regtest_headers, SAFE_BLOCK_INDEX};
+    use zk_light_client_lib::mmr::BlockHashesMmr;
+    use zk_light_client_lib::proof::{build_chunk_inputs, build_epoch_state};
+    use zk_light_client_lib::AsOptimizedBlock;
+
+    const CHUNK_VKEY_DIGEST: [u32; 8] = [7; 8];
+
+    // regtest blocks from the safe block, and its epoch state
+    fn chain(num_blocks: usize) -> (Vec<Header>, EpochState) {
+        let headers = mine_regtest_headers(SAFE_BLOCK_INDEX + num_blocks);
+        let safe_block_bits = headers[SAFE_BLOCK_INDEX].bits.to_consensus().to_le_bytes();
+        let epoch_state = build_epoch_state(
+            &headers[0],
+            0,
+            &headers[..=SAFE_BLOCK_INDEX],
+            block_work(safe_block_bits),
+        );
+        (headers[SAFE_BLOCK_INDEX..].to_vec(), epoch_state)
+    }
+
+    fn chunk_inputs(num_blocks: usize, mmr: &mut BlockHashesMmr) -> Vec<CircuitInput> {
+        let (blocks, epoch_state) = chain(num_blocks);
+        build_chunk_inputs(
+            SAFE_BLOCK_INDEX as u64,
+            &blocks,
+            epoch_state,
+            mmr,
+            Anchor::Chained,
+            Network::Regtest,
+            CommitmentScheme::Sha256,
+        )
+    }
+
+    // the chunks as given to the aggregation program, whose proofs are verified by the zkVM
+    fn aggregation_input(num_blocks: usize) -> AggregationInput {
+        let mut mmr = BlockHashesMmr::new(SAFE_BLOCK_INDEX as u64);
+        let chunks = chunk_inputs(num_blocks, &mut mmr)
+            .into_iter()
+            .map(|input| AggregatedChunk {
+                block_hashes: input.blocks[0..input.utilized_blocks as usize]
+                    .iter()
+                    .map(|block| block.compute_block_hash())
+                    .collect(),
+                public_values: validate_block(input),
+            })
+            .collect();
+        AggregationInput {
+            chunk_vkey_digest: CHUNK_VKEY_DIGEST,
+            chunks,
+        }
+    }
+
+    fn chunk(input: &mut AggregationInput, index: usize) -> &mut CircuitPublicValues {
+        &mut input.chunks[index].public_values
+    }
+
+    #[test]
+    fn test_build_chunk_inputs() {
+        let mut mmr = BlockHashesMmr::new(SAFE_BLOCK_INDEX as u64);
+        let inputs = chunk_inputs(2 * MAX_BLOCKS + 10, &mut mmr);
+        // chunks share their tip and safe block
+        assert_eq!(
+            inputs
+                .iter()
+                .map(|input| input.utilized_blocks)
+                .collect::<Vec<_>>(),
+            vec![MAX_BLOCKS as u64, MAX_BLOCKS as u64, 12]
+        );
+
+        let public_values = inputs
+            .iter()
+            .cloned()
+            .map(validate_block)
+            .collect::<Vec<_>>();
+        for (previous, next) in public_values.iter().zip(&public_values[1..]) {
+            assert_eq!(next.safe_block_hash, previous.tip_block_hash);
+            assert_eq!(next.safe_block_height, previous.tip_block_height);
+            assert_eq!(next.epoch_state_hash, previous.tip_epoch_state_hash);
+            assert_eq!(next.prev_mmr_root, previous.mmr_root);
+        }
+        assert_eq!(
+            public_values[2].tip_block_height,
+            SAFE_BLOCK_INDEX as u64 + 2 * MAX_BLOCKS as u64 + 9
+        );
+        assert_eq!(public_values[2].mmr_root, mmr.root());
+        assert_eq!(mmr.leaf_count(), 2 * MAX_BLOCKS as u64 + 10);
+        assert!(inputs[1..]
+            .iter()
+            .all(|input| matches!(input.anchor, Anchor::Chained)));
+    }
+
+    #[test]
+    fn test_single_chunk() {
+        let inputs = chunk_inputs(20, &mut BlockHashesMmr::new(SAFE_BLOCK_INDEX as u64));
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].utilized_blocks, 20);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let input = aggregation_input(2 * MAX_BLOCKS + 10);
+        let chunks = input.chunks.clone();
+        let aggregated = AggregationPublicValues::abi_decode(&aggregate(input), true).unwrap();
+        assert_eq!(aggregated.chunk_count, 3);
+        assert_eq!(
+            aggregated.chunk_vkey_digest.0,
+            vkey_digest_to_bytes(&CHUNK_VKEY_DIGEST)
+        );
+
+        let public_values = CircuitPublicValues::from_abi_public_inputs(aggregated.chain).unwrap();
+        let (first, last) = (&chunks[0].public_values, &chunks[2].public_values);
+        assert_eq!(public_values.safe_block_hash, first.safe_block_hash);
+        assert_eq!(public_values.epoch_state_hash, first.epoch_state_hash);
+        assert_eq!(public_values.prev_mmr_root, first.prev_mmr_root);
+        assert_eq!(public_values.tip_block_hash, last.tip_block_hash);
+        assert_eq!(public_values.tip_chainwork, last.tip_chainwork);
+        assert_eq!(
+            public_values.tip_epoch_state_hash,
+            last.tip_epoch_state_hash
+        );
+        assert_eq!(public_values.mmr_root, last.mmr_root);
+
+        // every block hash is committed once
+        let (blocks, _) = chain(2 * MAX_BLOCKS + 10);
+        let block_hashes = blocks
+            .iter()
+            .enumerate()
+            .map(|(i, header)| {
+                header
+                    .as_optimized_block((SAFE_BLOCK_INDEX + i) as u64)
+                    .compute_block_hash()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            public_values.block_hashes_merkle_root,
+            CommitmentScheme::Sha256.block_hashes_root(&block_hashes)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk safe block is not the previous chunk tip")]
+    fn test_missing_chunk() {
+        let mut input = aggregation_input(2 * MAX_BLOCKS + 10);
+        input.chunks.remove(1);
+        aggregate(input);
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk safe block height is not the previous chunk tip height")]
+    fn test_height_gap() {
+        let mut input = aggregation_input(MAX_BLOCKS + 10);
+        chunk(&mut input, 1).safe_block_height += 1;
+        chunk(&mut input, 1).tip_block_height += 1;
+        aggregate(input);
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk epoch state is not the previous chunk tip state")]
+    fn test_epoch_state_mismatch() {
+        let mut input = aggregation_input(MAX_BLOCKS + 10);
+        chunk(&mut input, 1).epoch_state_hash[0] ^= 1;
+        aggregate(input);
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk MMR does not extend the previous chunk MMR")]
+    fn test_mmr_root_mismatch() {
+        let mut input = aggregation_input(MAX_BLOCKS + 10);
+        chunk(&mut input, 1).prev_mmr_root[0] ^= 1;
+        aggregate(input);
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk domain tag mismatch")]
+    fn test_domain_mismatch() {
+        let mut input = aggregation_input(MAX_BLOCKS + 10);
+        chunk(&mut input, 1).domain_tag = DomainTag::new(Network::Testnet);
+        aggregate(input);
+    }
+
+    #[test]
+    #[should_panic(expected = "First chunk is anchored on a proof of another program")]
+    fn test_anchor_vkey_mismatch() {
+        let mut input = aggregation_input(MAX_BLOCKS + 10);
+        chunk(&mut input, 0).anchor_vkey_digest = vkey_digest_to_bytes(&[8; 8]);
+        aggregate(input);
+    }
+
+    #[test]
+    fn test_anchor_vkey() {
+        let mut input = aggregation_input(MAX_BLOCKS + 10);
+        chunk(&mut input, 0).anchor_vkey_digest = vkey_digest_to_bytes(&CHUNK_VKEY_DIGEST);
+        aggregate(input);
+    }
+}
+
+#[cfg(test)]
+mod vkeys {
+    use zk_light_client_lib::to_hex_string;
+    use zk_light_client_lib::vkey::{
+        parse_pinned_vkey_hash, pinned_vkey_file, pinned_vkey_hash, pinned_vkeys_dir,
+    };
+
+    #[test]
+    fn test_checked_in_vkeys() {
+        // the hashes can only be computed by the SP1 prover setup, the files may not hold one yet
+        for name in ["main", "aggregation"] {
+            assert!(pinned_vkeys_dir().join(format!("{name}.vkey")).exists());
+            pinned_vkey_hash(name);
+        }
+    }
+
+    #[test]
+    fn test_pinned_vkey_file() {
+        let vkey_hash = [7u8; 32];
+        assert_eq!(
+            parse_pinned_vkey_hash(&pinned_vkey_file("main", vkey_hash)),
+            Some(vkey_hash)
+        );
+        assert_eq!(
+            parse_pinned_vkey_hash(&format!("0x{}\n", to_hex_string(&vkey_hash))),
+            Some(vkey_hash)
+        );
+        assert_eq!(parse_pinned_vkey_hash("# not pinned yet\n\n"), None);
+        assert_eq!(parse_pinned_vkey_hash(""), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid pinned vkey hash")]
+    fn test_invalid_pinned_vkey() {
+        parse_pinned_vkey_hash("# main\n0x1234\n");
+    }
+}
+
+#[cfg(test)]
+mod domain {
+    use alloy_sol_types::SolType;
+    use zk_light_client_core::constants::{PROGRAM_VERSION, RULE_CHECKPOINTS};
+    use zk_light_client_core::genesis::Network;
+    use zk_light_client_core::{
+        validate_block, CircuitInput, CircuitPublicValues, DomainTag, ProofPublicInputs,
+    };
+    use zk_light_client_lib::headers::get_regtest_circuit_input;
+    use zk_light_client_lib::proof::{check_domain, PublicValuesError};
+
+    // the domain of `input` with one of its fields changed
+    fn foreign_domains() -> [DomainTag; 3] {
+        let domain_tag = DomainTag::new(Network::Regtest);
+        [
+            DomainTag {
+                network_magic: Network::Bitcoin.magic(),
+                ..domain_tag
+            },
+            DomainTag {
+                program_version: PROGRAM_VERSION + 1,
+                ..domain_tag
+            },
+            DomainTag {
+                rule_flags: domain_tag.rule_flags ^ RULE_CHECKPOINTS,
+                ..domain_tag
+            },
+        ]
+    }
+
+    fn input_with_domain(domain_tag: DomainTag) -> CircuitInput {
+        let mut input = get_regtest_circuit_input(5);
+        input.public_values.domain_tag = domain_tag;
+        input
+    }
+
+    #[test]
+    fn test_domain() {
+        let public_values = validate_block(get_regtest_circuit_input(5));
+        assert_eq!(public_values.domain_tag, DomainTag::new(Network::Regtest));
+        assert!(check_domain(&public_values, Network::Regtest).is_ok());
+        assert!(matches!(
+            check_domain(&public_values, Network::Bitcoin),
+            Err(PublicValuesError::DomainMismatch)
+        ));
+        for domain_tag in foreign_domains() {
+            let public_values = input_with_domain(domain_tag).public_values;
+            assert!(matches!(
+                check_domain(&public_values, Network::Regtest),
+                Err(PublicValuesError::DomainMismatch)
+            ));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Domain tag mismatch")]
+    fn test_other_network_magic() {
+        validate_block(input_with_domain(foreign_domains()[0]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Domain tag mismatch")]
+    fn test_other_program_version() {
+        validate_block(input_with_domain(foreign_domains()[1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Domain tag mismatch")]
+    fn test_other_rule_flags() {
+        validate_block(input_with_domain(foreign_domains()[2]));
+    }
+
+    #[test]
+    fn test_abi_round_trip() {
+        let mut public_values = validate_block(get_regtest_circuit_input(5));
+        public_values.prev_mmr_root = [1u8; 32];
+        public_values.checkpoints_root = [2u8; 32];
+        public_values.anchor_vkey_digest = [3u8; 32];
+        let bytes = public_values.abi_encode();
+
+        // the static fields of `ProofPublicInputs`, one word each
+        assert_eq!(bytes.len(), 17 * 32);
+        let public_inputs = ProofPublicInputs::abi_decode(&bytes, true).unwrap();
+        assert_eq!(
+            public_inputs.safe_block_hash.0,
+            public_values.safe_block_hash
+        );
+        assert_eq!(
+            public_inputs.tip_block_height,
+            public_values.tip_block_height
+        );
+        assert_eq!(
+            public_inputs.tip_chainwork.to_be_bytes::<32>(),
+            public_values.tip_chainwork
+        );
+        assert_eq!(public_inputs.prev_mmr_root.0, [1u8; 32]);
+        assert_eq!(public_inputs.checkpoints_root.0, [2u8; 32]);
+        assert_eq!(public_inputs.anchor_vkey_digest.0, [3u8; 32]);
+        assert_eq!(public_inputs.network_magic.0, Network::Regtest.magic());
+        assert_eq!(public_inputs.program_version, PROGRAM_VERSION);
+        assert_eq!(
+            public_inputs.rule_flags,
+            public_values.domain_tag.rule_flags
+        );
+        // `bytes4` is left-aligned in its word
+        assert_eq!(bytes[14 * 32..14 * 32 + 4], Network::Regtest.magic());
+
+        let decoded = CircuitPublicValues::abi_decode(&bytes).unwrap();
+        assert_eq!(decoded.abi_encode(), bytes);
+        assert_eq!(decoded.domain_tag, public_values.domain_tag);
+        assert_eq!(decoded.verification_mode, public_values.verification_mode);
+        assert_eq!(decoded.commitment_scheme, public_values.commitment_scheme);
+        assert_eq!(
+            ProofPublicInputs::abi_encode(&decoded.abi_public_inputs()),
+            ProofPublicInputs::abi_encode(&public_inputs)
+        );
+    }
+
+    #[test]
+    fn test_abi_decode_rejections() {
+        let bytes = validate_block(get_regtest_circuit_input(5)).abi_encode();
+        assert!(CircuitPublicValues::abi_decode(&bytes[..bytes.len() - 1]).is_err());
+        // unknown commitment scheme and verification mode
+        for field in [8, 13] {
+            let mut bytes = bytes.clone();
+            bytes[field * 32 + 31] = 0xff;
+            assert!(CircuitPublicValues::abi_decode(&bytes).is_err());
+        }
+    }
+}
+
+#[cfg(test)]
+mod block_hashes_commitment {
+    use crate::data::{block_hash, hash};
+    use bitcoin::hashes::{sha256, Hash};
+    use zk_light_client_core::commitment::CommitmentScheme;
+    use zk_light_client_core::sha256_merkle::get_merkle_root;
+
+    fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+        sha256::Hash::hash(&parts.concat()).to_byte_array()
+    }
+
+    // genesis and the next two mainnet blocks
+    fn mainnet_block_hashes() -> Vec<[u8; 32]> {
+        (0..3).map(block_hash).collect()
+    }
+
+    #[test]
+    fn test_mainnet_block_hashes() {
+        assert_eq!(
+            mainnet_block_hashes(),
+            [
+                hash("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"),
+                hash("00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048"),
+                hash("000000006a625f06636b8bb6ac7b960a8d03705d1ace08b1a19da3fdcc99ddbd"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tagged_tree() {
+        // the tree spelled out with another sha256 implementation
+        let block_hashes = mainnet_block_hashes();
+        let leaf = |block_hash: &[u8; 32]| sha256(&[&[0x00], block_hash]);
+        let node = |left: [u8; 32], right: [u8; 32]| sha256(&[&[0x01], &left, &right]);
+        let tree_root = node(
+            node(leaf(&block_hashes[0]), leaf(&block_hashes[1])),
+            // promoted, not paired with itself
+            leaf(&block_hashes[2]),
+        );
+        let root = sha256(&[&[0x02], &3u64.to_be_bytes(), &tree_root]);
+        assert_eq!(
+            CommitmentScheme::Sha256.block_hashes_root(&block_hashes),
+            root
+        );
+
+        for (index, block_hash) in block_hashes.iter().enumerate() {
+            let branch = CommitmentScheme::Sha256.block_hashes_branch(&block_hashes, index);
+            assert!(CommitmentScheme::Sha256.verify_block_hashes_branch(
+                root,
+                *block_hash,
+                index as u64,
+                3,
+                &branch
+            ));
+        }
+    }
+
+    #[test]
+    fn test_cve_2012_2459() {
+        // the transaction merkle tree pairs the last node of an odd level with itself, so the
+        // list with its last block hash repeated has the same root
+        let block_hashes = mainnet_block_hashes();
+        let mut mutated = block_hashes.clone();
+        mutated.push(block_hashes[2]);
+        assert_eq!(
+            get_merkle_root(block_hashes.clone()),
+            get_merkle_root(mutated.clone())
+        );
+
+        let root = CommitmentScheme::Sha256.block_hashes_root(&block_hashes);
+        assert_ne!(root, CommitmentScheme::Sha256.block_hashes_root(&mutated));
+        // nor is the repeated block hash provable against the original root
+        let branch = CommitmentScheme::Sha256.block_hashes_branch(&mutated, 3);
+        assert!(!CommitmentScheme::Sha256.verify_block_hashes_branch(
+            root,
+            block_hashes[2],
+            3,
+            4,
+            &branch
+        ));
+        assert!(!CommitmentScheme::Sha256.verify_block_hashes_branch(
+            root,
+            block_hashes[2],
+            3,
+            3,
+            &branch
+        ));
+    }
+}
+
+#[cfg(test)]
+mod keccak_block_hashes_commitment {
+    use crate::data::{block_hash, hash};
+    use zk_light_client_core::commitment::CommitmentScheme;
+    use zk_light_client_core::keccak_merkle::keccak256_hash;
+
+    // `keccak256(abi.encodePacked(parts))`
+    fn keccak256(parts: &[&[u8]]) -> [u8; 32] {
+        keccak256_hash(&parts.concat())
+    }
+
+    #[test]
+    fn test_keccak256() {
+        // Ethereum's keccak256, not the standardized SHA3-256
+        assert_eq!(
+            keccak256_hash(b""),
+            hash("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470")
+        );
+        assert_eq!(
+            keccak256_hash(b"abc"),
+            hash("4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45")
+        );
+    }
+
+    #[test]
+    fn test_tagged_tree() {
+        // mainnet block hashes, 5 leaves promote a node on two levels
+        let mut block_hashes = [0, 1, 2, 100000].map(block_hash).to_vec();
+        // parent of block 100000
+        block_hashes.push(hash(
+            "000000000002d01c1fccc21636b607dfd930d31d01c3a62104612a1719011250",
+        ));
+        let leaf = |block_hash: &[u8; 32]| keccak256(&[&[0x00], block_hash]);
+        let node = |left: [u8; 32], right: [u8; 32]| keccak256(&[&[0x01], &left, &right]);
+        let tree_root = node(
+            node(
+                node(leaf(&block_hashes[0]), leaf(&block_hashes[1])),
+                node(leaf(&block_hashes[2]), leaf(&block_hashes[3])),
+            ),
+            leaf(&block_hashes[4]),
+        );
+        let root = keccak256(&[&[0x02], &5u64.to_be_bytes(), &tree_root]);
+        assert_eq!(
+            CommitmentScheme::Keccak256.block_hashes_root(&block_hashes),
+            root
+        );
+        assert_ne!(
+            CommitmentScheme::Sha256.block_hashes_root(&block_hashes),
+            root
+        );
+
+        for (index, block_hash) in block_hashes.iter().enumerate() {
+            let branch = CommitmentScheme::Keccak256.block_hashes_branch(&block_hashes, index);
+            assert!(CommitmentScheme::Keccak256.verify_block_hashes_branch(
+                root,
+                *block_hash,
+                index as u64,
+                5,
+                &branch
+            ));
+            // the promoted leaf has no sibling until the last level
+            assert_eq!(branch.len(), if index == 4 { 1 } else { 3 });
+            assert!(!CommitmentScheme::Sha256.verify_block_hashes_branch(
+                root,
+                *block_hash,
+                index as u64,
+                5,
+                &branch
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod full_blocks {
+    use crate::data::{block_100000_txids, hash, header, load, mainnet_block, MAINNET_BLOCK};
+    use zk_light_client_core::block_contents::assert_transactions_root;
+    use zk_light_client_core::btc_light_client::AsLittleEndianBytes;
+    use zk_light_client_core::sha256_merkle::get_merkle_root;
+    use zk_light_client_core::transaction::{Reader, Transaction};
+    use zk_light_client_lib::AsOptimizedBlock;
+
+    #[test]
+    fn test_mainnet_block() {
+        let bytes = load(MAINNET_BLOCK);
+        let header: [u8; 80] = bytes[..80].try_into().unwrap();
+        // the height is not checked against the transactions
+        let header = header.as_optimized_block(0);
+        assert_eq!(
+            header.compute_block_hash(),
+            hash("00000000b0c5a240b2a61d2e75692224efd4cbecdf6eaf4cc2cf477ca7c270e7")
+        );
+
+        let mut reader = Reader::new(&bytes[80..]);
+        let txids = (0..reader.read_compact_size())
+            .map(|_| Transaction::read(&mut reader).txid())
+            .collect::<Vec<_>>();
+        assert!(reader.is_empty());
+        // as computed by rust-bitcoin
+        let expected_txids = mainnet_block()
+            .txdata
+            .iter()
+            .map(|transaction| hash(&transaction.compute_txid().to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(txids, expected_txids);
+        assert_eq!(txids.len(), 2);
+        assert_transactions_root(&txids, &header);
+    }
+
+    #[test]
+    fn test_block_100000() {
+        assert_transactions_root(
+            &block_100000_txids(),
+            &header(100000).as_optimized_block(100000),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Transactions don't match the header merkle root")]
+    fn test_reordered_transactions() {
+        let mut txids = block_100000_txids();
+        txids.swap(1, 2);
+        assert_transactions_root(&txids, &header(100000).as_optimized_block(100000));
+    }
+
+    #[test]
+    #[should_panic(expected = "Mutated transaction merkle tree")]
+    fn test_cve_2012_2459() {
+        // header committing to the first three transactions of block 100000, the third one is
+        // paired with itself
+        let txids = block_100000_txids()[..3].to_vec();
+        let mut header = header(100000).as_optimized_block(100000);
+        header.merkle_root = get_merkle_root(txids.clone()).to_little_endian();
+        assert_transactions_root(&txids, &header);
+
+        // the third transaction repeated gives the same root
+        let mut mutated = txids.clone();
+        mutated.push(txids[2]);
+        assert_eq!(get_merkle_root(mutated.clone()), get_merkle_root(txids));
+        assert_transactions_root(&mutated, &header);
+    }
+}
+
+#[cfg(test)]
+mod coinbase_heights {
+    use crate::data::{hash, load, regtest_block, REGTEST_BLOCK_HEIGHT};
+    use bitcoin::consensus::encode::serialize;
+    use zk_light_client_core::block_contents::bip34_height_push;
+    use zk_light_client_core::transaction::Transaction;
+
+    const TESTNET_COINBASE: &str =
+        "testnet_tx_4be105f158ea44aec57bf12c5817d073a712ab131df6f37786872cfc70734188";
+    // coinbase of block 100000
+    const MAINNET_COINBASE: &str =
+        "mainnet_tx_8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87";
+
+    fn coinbase_script_sig(bytes: &[u8]) -> Vec<u8> {
+        let coinbase = Transaction::parse(bytes);
+        assert!(coinbase.is_coinbase());
+        coinbase.inputs()[0].script_sig.to_vec()
+    }
+
+    #[test]
+    fn test_script_num_encoding() {
+        // `CScript() << nHeight` as in Bitcoin Core, around the push opcodes and sign bits
+        for height in [
+            0u64, 1, 16, 17, 127, 128, 255, 256, 32767, 32768, 227931, 8388607, 8388608, 924634,
+        ] {
+            assert_eq!(
+                bip34_height_push(height),
+                bitcoin::script::Builder::new()
+                    .push_int(height as i64)
+                    .into_script()
+                    .into_bytes(),
+                "{height}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_regtest_block() {
+        let block = regtest_block();
+        assert_eq!(block.bip34_block_height(), Ok(REGTEST_BLOCK_HEIGHT));
+        let script_sig = coinbase_script_sig(&serialize(&block.txdata[0]));
+        assert!(script_sig.starts_with(&bip34_height_push(REGTEST_BLOCK_HEIGHT)));
+        assert!(!script_sig.starts_with(&bip34_height_push(REGTEST_BLOCK_HEIGHT - 1)));
+    }
+
+    #[test]
+    fn test_testnet_coinbase() {
+        let bytes = load(TESTNET_COINBASE);
+        assert_eq!(
+            Transaction::parse(&bytes).txid(),
+            hash("4be105f158ea44aec57bf12c5817d073a712ab131df6f37786872cfc70734188")
+        );
+        let script_sig = coinbase_script_sig(&bytes);
+        assert!(script_sig.starts_with(&[0x03, 0xda, 0x1b, 0x0e]));
+        assert!(script_sig.starts_with(&bip34_height_push(924634)));
+    }
+
+    #[test]
+    fn test_pre_bip34_coinbase() {
+        // before BIP34 the coinbase of block 100000 pushes the bits of its header instead
+        let bytes = load(MAINNET_COINBASE);
+        assert_eq!(
+            Transaction::parse(&bytes).txid(),
+            hash("8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87")
+        );
+        let script_sig = coinbase_script_sig(&bytes);
+        assert!(script_sig.starts_with(&[0x04, 0x4c, 0x86, 0x04, 0x1b]));
+        assert!(!script_sig.starts_with(&bip34_height_push(100000)));
+    }
+}
+
+#[cfg(test)]
+mod witness_commitments {
+    use crate::data::{hash, header_input, load, regtest_block, REGTEST_BLOCK_HEIGHT};
+    use bitcoin::consensus::encode::serialize;
+    use zk_light_client_core::block_contents::{
+        assert_witness_inclusion, compute_witness_commitment, witness_commitment,
+    };
+    use zk_light_client_core::genesis::Network;
+    use zk_light_client_core::sha256_merkle::get_merkle_root;
+    use zk_light_client_core::transaction::Transaction;
+    use zk_light_client_lib::block_contents::build_witness_proof;
+
+    const SEGWIT_TRANSACTION: &str =
+        "mainnet_tx_f5864806e3565c34d1b41e716f72609d00b55ea5eac5b924c9719a842ef42206";
+    const LEGACY_TRANSACTION: &str =
+        "mainnet_tx_a6eab3c14ab5272a58a5ba91505ba1a4b6d7a3a9fcbd187b6cd99a7b6d548cb7";
+
+    #[test]
+    fn test_segwit_transaction() {
+        let bytes = load(SEGWIT_TRANSACTION);
+        let transaction = Transaction::parse(&bytes);
+        assert!(transaction.is_segwit());
+        assert_eq!(
+            transaction.txid(),
+            hash("f5864806e3565c34d1b41e716f72609d00b55ea5eac5b924c9719a842ef42206")
+        );
+        assert_eq!(
+            transaction.wtxid(),
+            hash("80b7d8a82d5d5bf92905b06f2014dd699e03837ca172e3a59d51426ebbe3e7f5")
+        );
+
+        let inputs = transaction.inputs();
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(
+            inputs[0].prev_txid,
+            hash("7cac3cf9a112cf04901a51d605058615d56ffe6d04b45270e89d1720ea955859")
+        );
+        assert_eq!(inputs[0].prev_vout, 1);
+        assert!(inputs[0].script_sig.is_empty());
+        // P2WPKH signature and public key
+        let witnesses = transaction.witnesses();
+        assert_eq!(witnesses.len(), 1);
+        assert_eq!(witnesses[0].len(), 2);
+        assert_eq!(witnesses[0][1].len(), 33);
+        let outputs = transaction.outputs();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].value, 506078);
+        assert_eq!(
+            outputs[0].script_pubkey,
+            hex::decode("a9140f3444e271620c736808aa7b33e370bd87cb5a0787").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_legacy_transaction() {
+        let bytes = load(LEGACY_TRANSACTION);
+        let transaction = Transaction::parse(&bytes);
+        assert!(!transaction.is_segwit());
+        let txid = hash("a6eab3c14ab5272a58a5ba91505ba1a4b6d7a3a9fcbd187b6cd99a7b6d548cb7");
+        assert_eq!(transaction.txid(), txid);
+        assert_eq!(transaction.wtxid(), txid);
+    }
+
+    #[test]
+    fn test_regtest_witness_commitment() {
+        let block = regtest_block();
+        let wtxids = block
+            .txdata
+            .iter()
+            .map(|transaction| {
+                let bytes = serialize(transaction);
+                Transaction::parse(&bytes).wtxid()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            wtxids[1],
+            hash("3c8d7eff789992c75db9542a53d19b6ede7d844dea42c2a0782c3e3694c7bace")
+        );
+
+        // the coinbase wtxid is replaced by zero in the witness tree
+        let coinbase_bytes = serialize(&block.txdata[0]);
+        let coinbase = Transaction::parse(&coinbase_bytes);
+        let witness_root = get_merkle_root(vec![[0u8; 32], wtxids[1]]);
+        assert_eq!(
+            witness_commitment(&coinbase),
+            Some(compute_witness_commitment(
+                witness_root,
+                coinbase.witnesses()[0][0]
+            ))
+        );
+        assert_eq!(
+            witness_commitment(&coinbase).unwrap(),
+            hash("4a3d9f3343dafcc0d6f6d4310f2ee5ce273ed34edca6c75db3a73e7f36873420")
+        );
+
+        let input = header_input(&block, REGTEST_BLOCK_HEIGHT, Network::Regtest);
+        let proof = build_witness_proof(&block, REGTEST_BLOCK_HEIGHT, 1);
+        assert_eq!(assert_witness_inclusion(&input, &proof).wtxid(), wtxids[1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Transaction is not committed by the coinbase")]
+    fn test_regtest_tampered_witness() {
+        let block = regtest_block();
+        let mut proof = build_witness_proof(&block, REGTEST_BLOCK_HEIGHT, 1);
+        // last byte of the last witness item, before the lock time
+        let position = proof.transaction.len() - 5;
+        proof.transaction[position] ^= 1;
+        assert_witness_inclusion(
+            &header_input(&block, REGTEST_BLOCK_HEIGHT, Network::Regtest),
+            &proof,
+        );
+    }
+}
+
+#[cfg(test)]
+mod payments {
+    use crate::data::{hash, header_input, mainnet_block};
+    use zk_light_client_core::block_contents::assert_transaction_inclusion;
+    use zk_light_client_core::genesis::Network;
+    use zk_light_client_lib::block_contents::{build_payment_proof, build_transaction_proof};
+
+    // the height of the block is not needed to check its transactions
+    const HEIGHT: u64 = 0;
+
+    #[test]
+    fn test_mainnet_payment() {
+        let block = mainnet_block();
+        let proof = build_payment_proof(&block, HEIGHT, 1, 0);
+        let input = header_input(&block, HEIGHT, Network::Bitcoin);
+        let transaction = assert_transaction_inclusion(&input, &proof.transaction);
+        assert_eq!(
+            transaction.txid(),
+            hash(&block.txdata[1].compute_txid().to_string())
+        );
+
+        // 61 BTC paid to a public key
+        let output = transaction.outputs()[proof.vout as usize];
+        assert_eq!(output.value, 6_100_000_000);
+        assert_eq!(
+            output.script_pubkey,
+            block.txdata[1].output[0].script_pubkey.as_bytes()
+        );
+        assert_eq!(output.script_pubkey.len(), 67);
+    }
+
+    #[test]
+    #[should_panic(expected = "Transaction is not included in the block")]
+    fn test_mainnet_payment_wrong_index() {
+        let block = mainnet_block();
+        let mut proof = build_transaction_proof(&block, HEIGHT, 1);
+        proof.index = 0;
+        assert_transaction_inclusion(&header_input(&block, HEIGHT, Network::Bitcoin), &proof);
+    }
+}
+
+#[cfg(test)]
+mod spends {
+    use crate::data::{hash, header_input, mainnet_block};
+    use zk_light_client_core::block_contents::assert_transaction_inclusion;
+    use zk_light_client_core::genesis::Network;
+    use zk_light_client_lib::block_contents::build_spend_proof;
+
+    const HEIGHT: u64 = 0;
+
+    // outpoints spent by the second transaction of the block
+    const SPENT_OUTPOINTS: [(&str, u32); 3] = [
+        (
+            "264299886446921c89e598ec2b1ec3eab6a2c9b0235b310ff513a039315ff721",
+            0,
+        ),
+        (
+            "4385fcf8b14497d0659adccfe06ae7e38e0b5dc95ff8a13d7c62035994a0cd79",
+            0,
+        ),
+        (
+            "828ef3b079f9c23829c56fe86e85b4a69d9e06e5b54ea597eef5fb3ffef509fe",
+            0,
+        ),
+    ];
+
+    #[test]
+    fn test_mainnet_spends() {
+        let block = mainnet_block();
+        let input = header_input(&block, HEIGHT, Network::Bitcoin);
+        for (vin, (txid, vout)) in SPENT_OUTPOINTS.iter().enumerate() {
+            let proof = build_spend_proof(&block, HEIGHT, 1, vin as u32);
+            let transaction = assert_transaction_inclusion(&input, &proof.transaction);
+            let inputs = transaction.inputs();
+            let spent = &inputs[proof.vin as usize];
+            assert_eq!(spent.prev_txid, hash(txid));
+            assert_eq!(spent.prev_vout, *vout);
+
+            let previous_output = block.txdata[1].input[vin].previous_output;
+            assert_eq!(spent.prev_txid, hash(&previous_output.txid.to_string()));
+            assert_eq!(spent.prev_vout, previous_output.vout);
+        }
+    }
+
+    #[test]
+    fn test_mainnet_coinbase_input() {
+        let block = mainnet_block();
+        let proof = build_spend_proof(&block, HEIGHT, 0, 0);
+        let input = header_input(&block, HEIGHT, Network::Bitcoin);
+        let transaction = assert_transaction_inclusion(&input, &proof.transaction);
+        assert!(transaction.is_coinbase());
+        assert!(transaction.inputs()[0].is_coinbase());
+        assert!(!block.txdata[1].is_coinbase());
+    }
+}
+
+#[cfg(test)]
+mod preimages {
+    use crate::data::{hash, header_input, regtest_block, REGTEST_BLOCK_HEIGHT};
+    use bitcoin::hashes::{sha256, Hash};
+    use zk_light_client_core::block_contents::assert_witness_inclusion;
+    use zk_light_client_core::genesis::Network;
+    use zk_light_client_core::sha256_merkle::sha256_hash;
+    use zk_light_client_lib::block_contents::build_preimage_proof;
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            sha256_hash(b""),
+            hash("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+        assert_eq!(
+            sha256_hash(b"abc"),
+            hash("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+    }
+
+    #[test]
+    fn test_regtest_witness_items() {
+        let block = regtest_block();
+        let input = header_input(&block, REGTEST_BLOCK_HEIGHT, Network::Regtest);
+        // both inputs of the second transaction reveal a signature and a public key
+        for vin in 0..2 {
+            for item in 0..2 {
+                let proof = build_preimage_proof(&block, REGTEST_BLOCK_HEIGHT, 1, vin, item);
+                let transaction = assert_witness_inclusion(&input, &proof.transaction);
+                let witnesses = transaction.witnesses();
+                let preimage = witnesses[proof.vin as usize][proof.item as usize];
+
+                let witness = &block.txdata[1].input[vin as usize].witness;
+                assert_eq!(preimage, &witness[item as usize]);
+                assert_eq!(
+                    sha256_hash(preimage),
+                    sha256::Hash::hash(&witness[item as usize]).to_byte_array()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod op_returns {
+    use crate::data::{hash, header, mainnet_block, regtest_block};
+    use bitcoin::hashes::Hash;
+    use bitcoin::script::{Instruction, Script};
+    use zk_light_client_core::block_contents::op_return_payload;
+
+    // OP_RETURN pushing `payload` with the given push opcode and length bytes
+    fn op_return(push: &[u8], payload: &[u8]) -> Vec<u8> {
+        [&[0x6a], push, payload].concat()
+    }
+
+    #[test]
+    fn test_push_opcodes() {
+        // a real 32-byte value, the merkle root of mainnet block 100000
+        let payload = header(100000).merkle_root.to_byte_array();
+        let scripts = [
+            op_return(&[0x20], &payload),
+            // OP_PUSHDATA1, OP_PUSHDATA2, OP_PUSHDATA4
+            op_return(&[0x4c, 0x20], &payload),
+            op_return(&[0x4d, 0x20, 0x00], &payload),
+            op_return(&[0x4e, 0x20, 0x00, 0x00, 0x00], &payload),
+        ];
+        for script in scripts.iter() {
+            assert_eq!(op_return_payload(script), payload);
+
+            let instructions = Script::from_bytes(script)
+                .instructions()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(instructions.len(), 2);
+            match instructions[1] {
+                Instruction::PushBytes(bytes) => {
+                    assert_eq!(op_return_payload(script), bytes.as_bytes())
+                }
+                _ => panic!("not a push"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_witness_commitment_output() {
+        let block = regtest_block();
+        let script_pubkey = block.txdata[0].output[1].script_pubkey.as_bytes();
+        // the BIP141 header followed by the commitment, too long to be anchored
+        let payload = op_return_payload(script_pubkey);
+        assert_eq!(payload.len(), 36);
+        assert_eq!(payload[..4], [0xaa, 0x21, 0xa9, 0xed]);
+        assert_eq!(
+            payload[4..],
+            hash("4a3d9f3343dafcc0d6f6d4310f2ee5ce273ed34edca6c75db3a73e7f36873420")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Output is not an OP_RETURN")]
+    fn test_payment_output() {
+        let block = mainnet_block();
+        op_return_payload(block.txdata[1].output[0].script_pubkey.as_bytes());
+    }
+
+    #[test]
+    #[should_panic(expected = "OP_RETURN holds more than one push")]
+    fn test_several_pushes() {
+        let payload = header(100000).merkle_root.to_byte_array();
+        op_return_payload(&[op_return(&[0x20], &payload), vec![0x01, 0x00]].concat());
+    }
+}
+
+#[cfg(test)]
+mod txout_proofs {
+    use crate::data::{block_100000_txids, hash, header};
+    use bitcoin::consensus::encode::serialize;
+    use bitcoin::hashes::Hash;
+    use bitcoin::merkle_tree::MerkleBlockError;
+    use bitcoin::{MerkleBlock, TxMerkleNode, Txid};
+    use zk_light_client_core::btc_light_client::AsLittleEndianBytes;
+    use zk_light_client_core::sha256_merkle::{get_merkle_root, hash_pairs};
+    use zk_light_client_lib::spv::{parse_electrum_merkle, parse_txout_proof, SpvError};
+
+    // serialized `CMerkleBlock` of the block of `header` matching the transactions at `indexes`,
+    // as returned by `gettxoutproof`
+    fn txout_proof(
+        header: bitcoin::block::Header,
+        txids: &[[u8; 32]],
+        indexes: &[usize],
+    ) -> Vec<u8> {
+        let txids = txids
+            .iter()
+            .map(|txid| Txid::from_byte_array(txid.to_little_endian()))
+            .collect::<Vec<_>>();
+        serialize(&MerkleBlock::from_header_txids_with_predicate(
+            &header,
+            &txids,
+            |txid| indexes.contains(&txids.iter().position(|other| other == txid).unwrap()),
+        ))
+    }
+
+    #[test]
+    fn test_single_match() {
+        let header = header(100000);
+        let txids = block_100000_txids();
+        let (parsed_header, branches) =
+            parse_txout_proof(&txout_proof(header, &txids, &[2])).unwrap();
+        assert_eq!(parsed_header, header);
+        assert_eq!(branches.len(), 1);
+        assert_eq!(branches[0].txid, txids[2]);
+        assert_eq!(branches[0].index, 2);
+        assert_eq!(
+            branches[0].branch,
+            vec![txids[3], hash_pairs(txids[0], txids[1])]
+        );
+        assert_eq!(
+            branches[0].merkle_root().to_little_endian(),
+            hash("f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766")
+        );
+    }
+
+    #[test]
+    fn test_multiple_matches() {
+        let header = header(100000);
+        let txids = block_100000_txids();
+        let (_, branches) = parse_txout_proof(&txout_proof(header, &txids, &[0, 3])).unwrap();
+        assert_eq!(
+            branches
+                .iter()
+                .map(|branch| branch.index)
+                .collect::<Vec<_>>(),
+            vec![0, 3]
+        );
+        assert_eq!(branches[0].txid, txids[0]);
+        assert_eq!(
+            branches[0].branch,
+            vec![txids[1], hash_pairs(txids[2], txids[3])]
+        );
+        assert_eq!(branches[1].txid, txids[3]);
+        assert_eq!(
+            branches[1].branch,
+            vec![txids[2], hash_pairs(txids[0], txids[1])]
+        );
+        for branch in branches.iter() {
+            assert_eq!(branch.merkle_root(), header.merkle_root.to_byte_array());
+        }
+    }
+
+    #[test]
+    fn test_odd_level() {
+        // the first three transactions of block 100000 under a header committing to them, so
+        // that the last node of each level is paired with itself
+        let mut header = header(100000);
+        let txids = block_100000_txids()[..3].to_vec();
+        header.merkle_root =
+            TxMerkleNode::from_byte_array(get_merkle_root(txids.clone()).to_little_endian());
+        let (_, branches) = parse_txout_proof(&txout_proof(header, &txids, &[2])).unwrap();
+        assert_eq!(
+            branches[0].branch,
+            vec![txids[2], hash_pairs(txids[0], txids[1])]
+        );
+        assert_eq!(
+            branches[0].merkle_root(),
+            header.merkle_root.to_byte_array()
+        );
+    }
+
+    #[test]
+    fn test_tampered_hash() {
+        let txids = block_100000_txids();
+        let mut bytes = txout_proof(header(100000), &txids, &[2]);
+        // header, transaction count and hash count precede the first hash
+        bytes[80 + 4 + 1] ^= 1;
+        assert!(matches!(
+            parse_txout_proof(&bytes),
+            Err(SpvError::PartialMerkleTree(
+                MerkleBlockError::MerkleRootMismatch
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_truncated_proof() {
+        let txids = block_100000_txids();
+        let bytes = txout_proof(header(100000), &txids, &[2]);
+        for len in [0, 79, 80 + 4 + 1 + 32, bytes.len() - 1] {
+            assert!(matches!(
+                parse_txout_proof(&bytes[..len]),
+                Err(SpvError::Decode(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_bad_flag_bits() {
+        let txids = block_100000_txids();
+        let bytes = txout_proof(header(100000), &txids, &[2]);
+        // the flag bytes end the proof, the first bits are those of the root and of its children,
+        // the next ones only mark matched leaves
+        for bit in 0..3 {
+            let mut bytes = bytes.clone();
+            *bytes.last_mut().unwrap() ^= 1 << bit;
+            assert!(matches!(
+                parse_txout_proof(&bytes),
+                Err(SpvError::PartialMerkleTree(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_unused_hash() {
+        let txids = block_100000_txids();
+        let mut bytes = txout_proof(header(100000), &txids, &[2]);
+        // one more hash than the tree walk consumes
+        bytes[80 + 4] += 1;
+        bytes.splice(80 + 4 + 1..80 + 4 + 1, txids[0]);
+        assert!(matches!(
+            parse_txout_proof(&bytes),
+            Err(SpvError::PartialMerkleTree(
+                MerkleBlockError::NotAllHashesConsumed
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_electrum_merkle() {
+        let txid = block_100000_txids()[2];
+        for json in [
+            r#"{"block_height": 100000, "merkle": ["00"], "pos": 2}"#,
+            r#"{"block_height": 100000, "merkle": ["zz"], "pos": 2}"#,
+        ] {
+            assert!(matches!(
+                parse_electrum_merkle(json, txid),
+                Err(SpvError::InvalidHash(_))
+            ));
+        }
+        assert!(matches!(
+            parse_electrum_merkle(r#"{"merkle": [], "pos": 2}"#, txid),
+            Err(SpvError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_electrum_merkle() {
+        let header = header(100000);
+        let txids = block_100000_txids();
+        let json = format!(
+            r#"{{"block_height": 100000, "merkle": ["{}", "{}"], "pos": 2}}"#,
+            hex::encode(txids[3]),
+            hex::encode(hash_pairs(txids[0], txids[1]))
+        );
+        let (height, branch) = parse_electrum_merkle(&json, txids[2]).unwrap();
+        assert_eq!(height, 100000);
+        assert_eq!(branch.merkle_root(), header.merkle_root.to_byte_array());
+        let (_, branches) = parse_txout_proof(&txout_proof(header, &txids, &[2])).unwrap();
+        assert_eq!(branch, branches[0]);
+    }
+}